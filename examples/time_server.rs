@@ -36,6 +36,7 @@ use slog::{Drain, Logger};
 use slog_term::{FullFormat, PlainSyncDecorator};
 
 use tokio_jsonrpc::{Endpoint, LineCodec, RpcError, Server, ServerCtl};
+use tokio_jsonrpc::message::Params;
 
 /// A helper struct to deserialize the parameters
 #[derive(Deserialize)]
@@ -68,7 +69,7 @@ impl Server for TimeServer {
     type NotificationResult = Result<(), ()>;
     /// The actual implementation of the RPC methods
     fn rpc(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::RpcCallResult> {
         match method {
             // Return the number of seconds since epoch (eg. unix timestamp)
@@ -93,7 +94,7 @@ impl Server for TimeServer {
                         // And send the notification on each tick (and pass the client through)
                         let notified = interval.fold(client, move |client, _| {
                                 debug!(logger_cloned, "Tick");
-                                client.notify("time".to_owned(), Some(json!([now()])))
+                                client.notify("time".to_owned(), Some(vec![json!(now())].into()))
                             })
                             // So it can be spawned, spawn needs ().
                             .map(|_| ())
@@ -12,28 +12,100 @@
 //! messages to be separated by newlines and not to contain newlines in their representation. On
 //! the other hand, it can recover from syntax error in a message and you can respond with an error
 //! instead of terminating the connection.
+//!
+//! All the codecs accept only JSON-RPC 2.0 messages by default. A differently configured
+//! [Parser](../message/struct.Parser.html) may be passed to their `with_parser` constructors (eg.
+//! to accept JSON-RPC 1.0 too).
+//!
+//! The parser also holds the resource limits. The codecs don't buffer more than the maximum size
+//! of a message, so a peer can't exhaust the memory by sending an endless one. Where the codec can
+//! find the end of the too large message without buffering it (the line ones and the binary ones),
+//! the message is thrown away and reported as
+//! [`Broken::LimitExceeded`](../message/enum.Broken.html#variant.LimitExceeded), unless the
+//! limits are [fatal](../message/struct.Parser.html#method.fatal_limits). Otherwise, the codec
+//! returns an I/O error.
+//!
+//! The [ContentLength](struct.ContentLength.html) codec frames the messages by headers, as the
+//! Language Server Protocol does. It doesn't care about the newlines inside the messages. If the
+//! peers may use different framings, [AutoDetect](struct.AutoDetect.html) picks one of these three
+//! by what arrives first. The
+//! [LengthPrefixed](struct.LengthPrefixed.html) and [Netstring](struct.Netstring.html) codecs
+//! prefix each message by its length, so finding its end needs no scanning.
+//!
+//! Besides JSON, the same messages can be encoded in binary formats, which are more compact. The
+//! [Cbor](struct.Cbor.html) codec is available with the `cbor` feature and the
+//! [MessagePack](struct.MessagePack.html) one with the `msgpack` feature. As the binary encodings
+//! can contain any byte, they prefix each message with its length instead of separating them.
 
+use std::cmp;
+use std::error::Error as StdError;
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+use std::fmt::Display;
 use std::io::{Error, ErrorKind, Result as IoResult};
+use std::mem;
 
-use tokio_io::codec::{Decoder, Encoder};
+use futures::{Poll, Sink, StartSend, Stream};
+use tokio_io::{AsyncRead, AsyncWrite};
+// The framing moved to tokio-codec, but the crate and its users build on the one in tokio_io
+#[allow(deprecated)]
+use tokio_io::codec::{Decoder, Encoder, FramedRead, FramedWrite};
 use bytes::{BufMut, BytesMut};
 use serde_json::de::Deserializer;
 use serde_json::ser::to_vec;
-use serde_json::error::Error as SerdeError;
 
-use message::{decoded_to_parsed, from_slice, from_str, Message, Parsed};
+use endpoint::Received;
+use message::{Broken, Limit, Message, Parsed, Parser, SyntaxError};
 
 /// A helper to wrap the error
-fn err_map(e: SerdeError) -> Error {
+fn err_map<E: StdError + Send + Sync + 'static>(e: E) -> Error {
     Error::new(ErrorKind::Other, e)
 }
 
-/// A helper trait to unify `Line` and `DirtyLine`
+/// The error returned when a limit is exceeded and the limits are fatal.
+fn limit_error(limit: Limit) -> Error {
+    Error::new(ErrorKind::InvalidData, limit.to_string())
+}
+
+/// A frame cut out of the input.
+enum Frame {
+    /// The frame, to be parsed.
+    Data(BytesMut),
+    /// A frame over the size limit, thrown away.
+    TooLarge,
+}
+
+/// Turns an exceeded limit into an error, if the parser says the limits are fatal.
+fn check_fatal(parser: &Parser, parsed: Parsed) -> IoResult<Parsed> {
+    match parsed {
+        Err(Broken::LimitExceeded(limit)) if parser.limits_fatal() => Err(limit_error(limit)),
+        parsed => Ok(parsed),
+    }
+}
+
+/// Parses a frame, if there's any.
+fn parse_frame<Parse>(
+    parser: &Parser, frame: Option<Frame>, parse: Parse
+) -> IoResult<Option<Parsed>>
+where
+    Parse: FnOnce(BytesMut) -> Parsed,
+{
+    let parsed = match frame {
+        None => return Ok(None),
+        Some(Frame::Data(data)) => parse(data),
+        Some(Frame::TooLarge) => Err(Broken::LimitExceeded(Limit::Size)),
+    };
+    check_fatal(parser, parsed).map(Some)
+}
+
+/// A helper trait to unify `Line`, `DirtyLine` and `RawLine`
 trait PositionCache {
     fn position(&mut self) -> &mut usize;
+    /// Set while throwing away the rest of a too large line.
+    fn skipping(&mut self) -> &mut bool;
+    fn parser(&self) -> &Parser;
 }
 
-/// An encoding function reused by [`Line`], [`DirtyLine`] and [`Boundary`]
+/// An encoding function reused by [`Line`], [`DirtyLine`], [`RawLine`] and [`Boundary`]
 fn encode_codec(msg: &Message, buf: &mut BytesMut) -> IoResult<()> {
     let encoded = to_vec(&msg).map_err(err_map)?;
     // As discovered the hard way, we must not overwrite buf, but append to it.
@@ -43,26 +115,40 @@ fn encode_codec(msg: &Message, buf: &mut BytesMut) -> IoResult<()> {
     Ok(())
 }
 
-fn decode_codec<Cache, Convert>(
-    cache: &mut Cache, buf: &mut BytesMut, convert: Convert
-) -> IoResult<Option<Parsed>>
-where
-    Cache: PositionCache,
-    Convert: FnOnce(&[u8]) -> Parsed,
-{
-    // Where did we stop scanning before? Scan only the new part
-    let start_pos = cache.position();
-    if let Some(i) = buf[*start_pos..].iter().position(|&b| b == b'\n') {
-        let end_pos = *start_pos + i;
-        let line = buf.split_to(end_pos);
-        buf.split_to(1);
-        // We'll start from the beginning next time.
-        *start_pos = 0;
-        Ok(Some(convert(&line)))
-    } else {
-        // Mark where we ended scanning.
-        *start_pos = buf.len();
-        Ok(None)
+fn decode_codec<Cache: PositionCache>(cache: &mut Cache, buf: &mut BytesMut) -> Option<Frame> {
+    loop {
+        // Where did we stop scanning before? Scan only the new part
+        let start_pos = *cache.position();
+        if let Some(i) = buf[start_pos..].iter().position(|&b| b == b'\n') {
+            let end_pos = start_pos + i;
+            let line = buf.split_to(end_pos);
+            buf.split_to(1);
+            // We'll start from the beginning next time.
+            *cache.position() = 0;
+            if mem::replace(cache.skipping(), false) {
+                // The end of a too large line, which was already reported. Try the next one.
+                continue;
+            }
+            if cache.parser().size_exceeded(line.len()) {
+                return Some(Frame::TooLarge);
+            }
+            return Some(Frame::Data(line));
+        }
+        *cache.position() = 0;
+        if *cache.skipping() {
+            // Still inside a too large line
+            buf.clear();
+        } else if cache.parser().size_exceeded(buf.len()) {
+            // Don't wait for the end of the line, it is too large already. Throw away what is
+            // here and the rest of the line as it comes.
+            buf.clear();
+            *cache.skipping() = true;
+            return Some(Frame::TooLarge);
+        } else {
+            // Mark where we ended scanning.
+            *cache.position() = buf.len();
+        }
+        return None;
     }
 }
 
@@ -73,19 +159,37 @@ where
 ///
 /// Note that the produced items is a `Result`, to allow not terminating the stream on
 /// protocol-level errors.
-#[derive(Debug, Default)]
-pub struct Line(usize);
+#[derive(Clone, Debug, Default)]
+pub struct Line {
+    position: usize,
+    skipping: bool,
+    parser: Parser,
+}
 
 impl Line {
     /// A constructor
     pub fn new() -> Self {
-        Line(0)
+        Line::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        Line {
+            position: 0,
+            skipping: false,
+            parser,
+        }
     }
 }
 
 impl PositionCache for Line {
     fn position(&mut self) -> &mut usize {
-        &mut self.0
+        &mut self.position
+    }
+    fn skipping(&mut self) -> &mut bool {
+        &mut self.skipping
+    }
+    fn parser(&self) -> &Parser {
+        &self.parser
     }
 }
 
@@ -101,7 +205,8 @@ impl Decoder for Line {
     type Item = Parsed;
     type Error = Error;
     fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
-        decode_codec(self, src, from_slice)
+        let frame = decode_codec(self, src);
+        parse_frame(&self.parser, frame, |bytes| self.parser.parse_slice(&bytes))
     }
 }
 
@@ -114,19 +219,37 @@ impl Decoder for Line {
 /// these special utf-8 WTF question marks (U+FFFD).
 ///
 /// In contrast, Line errors on such invalid inputs. Encoding is the same for both codecs, however.
-#[derive(Debug, Default)]
-pub struct DirtyLine(usize);
+#[derive(Clone, Debug, Default)]
+pub struct DirtyLine {
+    position: usize,
+    skipping: bool,
+    parser: Parser,
+}
 
 impl DirtyLine {
     /// A constructor
     pub fn new() -> Self {
-        DirtyLine(0)
+        DirtyLine::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        DirtyLine {
+            position: 0,
+            skipping: false,
+            parser,
+        }
     }
 }
 
 impl PositionCache for DirtyLine {
     fn position(&mut self) -> &mut usize {
-        &mut self.0
+        &mut self.position
+    }
+    fn skipping(&mut self) -> &mut bool {
+        &mut self.skipping
+    }
+    fn parser(&self) -> &Parser {
+        &self.parser
     }
 }
 
@@ -134,8 +257,10 @@ impl Decoder for DirtyLine {
     type Item = Parsed;
     type Error = Error;
     fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
-        decode_codec(self, src, |bytes| {
-            from_str(String::from_utf8_lossy(bytes).as_ref())
+        let frame = decode_codec(self, src);
+        parse_frame(&self.parser, frame, |bytes| {
+            self.parser
+                .parse_str(String::from_utf8_lossy(&bytes).as_ref())
         })
     }
 }
@@ -148,12 +273,176 @@ impl Encoder for DirtyLine {
     }
 }
 
+/// A codec producing raw newline-separated frames.
+///
+/// This works like the [Line](struct.Line.html) codec, but it doesn't parse the messages. It
+/// produces the bytes of each frame, cut out of the input buffer without copying. They can be
+/// parsed into a borrowed [MessageRef](../message/enum.MessageRef.html) by
+/// [`Parser::parse_ref`](../message/struct.Parser.html#method.parse_ref), which avoids building the
+/// owned messages. Encoding is the same as with `Line`.
+///
+/// The [`Endpoint`](../endpoint/struct.Endpoint.html) accepts the frames too. It parses them this
+/// way and passes the requests to the server with the parameters still raw (see
+/// [`Server::rpc_ref`](../server/trait.Server.html#method.rpc_ref)).
+///
+/// As this codec doesn't produce the broken messages, a frame over the size limit of the parser is
+/// always an I/O error.
+#[derive(Clone, Debug, Default)]
+pub struct RawLine {
+    position: usize,
+    skipping: bool,
+    parser: Parser,
+}
+
+impl RawLine {
+    /// A constructor
+    pub fn new() -> Self {
+        RawLine::default()
+    }
+    /// A constructor limiting the size of the frames by the parser.
+    ///
+    /// Only the size limit is used by the codec itself. The frames should be parsed by the same
+    /// parser, for the other limits to apply.
+    pub fn with_parser(parser: Parser) -> Self {
+        RawLine {
+            position: 0,
+            skipping: false,
+            parser,
+        }
+    }
+}
+
+impl PositionCache for RawLine {
+    fn position(&mut self) -> &mut usize {
+        &mut self.position
+    }
+    fn skipping(&mut self) -> &mut bool {
+        &mut self.skipping
+    }
+    fn parser(&self) -> &Parser {
+        &self.parser
+    }
+}
+
+impl Decoder for RawLine {
+    type Item = BytesMut;
+    type Error = Error;
+    fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<BytesMut>> {
+        match decode_codec(self, src) {
+            Some(Frame::Data(frame)) => Ok(Some(frame)),
+            Some(Frame::TooLarge) => Err(limit_error(Limit::Size)),
+            None => Ok(None),
+        }
+    }
+}
+
+impl Encoder for RawLine {
+    type Item = Message;
+    type Error = Error;
+    fn encode(&mut self, msg: Message, buf: &mut BytesMut) -> IoResult<()> {
+        encode_codec(&msg, buf)
+    }
+}
+
+/// What the [`Scanner`] found in the buffer.
+enum Scanned {
+    /// A value ending at the position (exclusive).
+    Complete(usize),
+    /// The value is not complete yet (or there's only whitespace so far).
+    Incomplete,
+    /// The value doesn't start with a bracket or a quote, so the scanner can't find its end.
+    Other,
+}
+
+/// An incremental scanner looking for the end of the first JSON value in a buffer.
+///
+/// It follows only the strings (with their escapes) and the brackets, not the whole syntax. It
+/// remembers where it stopped, so each byte is looked at once, no matter in how many pieces the
+/// value arrives. It needs to be reset whenever the start of the buffer is cut off.
+#[derive(Clone, Debug, Default)]
+struct Scanner {
+    position: usize,
+    start: Option<usize>,
+    depth: usize,
+    in_string: bool,
+    escaped: bool,
+}
+
+impl Scanner {
+    fn scan(&mut self, buf: &[u8]) -> Scanned {
+        for (i, &byte) in buf.iter().enumerate().skip(self.position) {
+            if self.in_string {
+                if self.escaped {
+                    self.escaped = false;
+                } else if byte == b'\\' {
+                    self.escaped = true;
+                } else if byte == b'"' {
+                    self.in_string = false;
+                    if self.depth == 0 {
+                        return Scanned::Complete(i + 1);
+                    }
+                }
+                continue;
+            }
+            match byte {
+                b'"' => {
+                    self.in_string = true;
+                    self.start.get_or_insert(i);
+                },
+                b'[' | b'{' => {
+                    self.depth += 1;
+                    self.start.get_or_insert(i);
+                },
+                // The end of the value (or a stray bracket, which fails to parse)
+                b']' | b'}' if self.depth <= 1 => return Scanned::Complete(i + 1),
+                b']' | b'}' => self.depth -= 1,
+                b' ' | b'\t' | b'\n' | b'\r' => (),
+                _ if self.start.is_none() => return Scanned::Other,
+                _ => (),
+            }
+        }
+        self.position = buf.len();
+        Scanned::Incomplete
+    }
+}
+
 /// A codec working with JSONRPC 2.0 messages.
 ///
 /// This produces or encodes [Message](../message/enum.Message.html). It takes the JSON object
 /// boundaries, so it works with both newline-separated and object-separated encoding. It produces
 /// newline-separated stream, which is more generic.
-pub struct Boundary;
+///
+/// The input is scanned incrementally and a message is parsed only once it is complete, so a large
+/// message arriving in many small pieces is still decoded in linear time. The exception is right
+/// after a syntax error, when the data is parsed each time until the codec is in sync again.
+///
+/// After a syntax error, the codec skips the damaged data up to the next `{` or `[` that may start
+/// a new message and tries again from there. Only one syntax error is reported for the whole
+/// damaged region, until something parses again.
+///
+/// The end of a message is not known until it is parsed, so an incomplete message over the size
+/// limit of the parser is always an I/O error. A complete one is reported as a broken message.
+#[derive(Clone, Debug, Default)]
+pub struct Boundary {
+    parser: Parser,
+    resyncing: bool,
+    scanner: Scanner,
+}
+
+impl Boundary {
+    /// A constructor
+    pub fn new() -> Self {
+        Boundary::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        Boundary {
+            parser,
+            resyncing: false,
+            scanner: Scanner::default(),
+        }
+    }
+}
 
 impl Encoder for Boundary {
     type Item = Message;
@@ -167,25 +456,805 @@ impl Decoder for Boundary {
     type Item = Parsed;
     type Error = Error;
     fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
-        let (decoded, pos) = {
-            let mut deserializer = Deserializer::from_slice(src).into_iter();
-            let decoded = deserializer.next().and_then(|result| match result {
-                Err(ref e) if e.is_eof() => None,
-                other => Some(decoded_to_parsed(other)),
+        loop {
+            let end = match self.scanner.scan(src) {
+                Scanned::Complete(end) => end,
+                // Most likely a number or other short value, let the parser find its end
+                Scanned::Other => src.len(),
+                Scanned::Incomplete => match self.scanner.start {
+                    None => {
+                        // Only whitespace, don't keep it around
+                        src.clear();
+                        self.scanner = Scanner::default();
+                        return Ok(None);
+                    },
+                    Some(start) if self.parser.size_exceeded(src.len() - start) => {
+                        return Err(limit_error(Limit::Size));
+                    },
+                    // After an error, the brackets in the garbage may be unbalanced and the end
+                    // never comes. Let the parser decide if it's garbage or just incomplete.
+                    Some(_) if self.resyncing => src.len(),
+                    Some(_) => return Ok(None),
+                },
+            };
+            // Whatever happens now, the start of the buffer gets cut off
+            self.scanner = Scanner::default();
+            let (decoded, pos) = {
+                let mut deserializer = Deserializer::from_slice(&src[..end]).into_iter();
+                let decoded = deserializer.next().and_then(|result| match result {
+                    Err(ref e) if e.is_eof() => None,
+                    other => Some(other),
+                });
+                (decoded, deserializer.byte_offset())
+            };
+            let parsed = match decoded {
+                None => None,
+                Some(Err(e)) => {
+                    // The position is at the start of the broken value. Skip at least one byte, so
+                    // we don't get stuck on the same error, and go to what may start a message.
+                    let next = src[cmp::min(pos + 1, src.len())..]
+                        .iter()
+                        .position(|&b| b == b'{' || b == b'[')
+                        .map_or(src.len(), |i| pos + 1 + i);
+                    src.split_to(next);
+                    if mem::replace(&mut self.resyncing, true) {
+                        // Still the same damaged region, already reported
+                        continue;
+                    }
+                    return Ok(Some(Err(Broken::SyntaxError(e.into()))));
+                },
+                // The JSON parser has its own recursion limit, so the depth is safe to check after
+                Some(decoded) => {
+                    self.resyncing = false;
+                    Some(
+                        self.parser
+                            .check_input(&src[..pos])
+                            .and_then(|()| self.parser.decoded_to_parsed(decoded)),
+                    )
+                },
+            };
+
+            // It did read some data from the input. Find out how many and cut them off.
+            src.split_to(pos);
+            return match parsed {
+                Some(parsed) => check_fatal(&self.parser, parsed).map(Some),
+                None => Ok(None),
+            };
+        }
+    }
+}
+
+/// Throws away as much of the `skip` bytes as there is in the buffer.
+fn skip_some(buf: &mut BytesMut, skip: &mut usize) {
+    let skipped = cmp::min(*skip, buf.len());
+    buf.split_to(skipped);
+    *skip -= skipped;
+}
+
+/// The most the codecs reserve in advance for the rest of a message of known length.
+///
+/// The length comes from the peer, so it can't be trusted with the allocation. The buffer grows
+/// further only as the data actually arrive.
+const MAX_RESERVE: usize = 64 * 1024;
+
+/// Makes room for the rest of a message of known length, up to `MAX_RESERVE`.
+fn reserve_some(buf: &mut BytesMut, missing: usize) {
+    buf.reserve(cmp::min(missing, MAX_RESERVE));
+}
+
+/// The maximum size of the headers the `ContentLength` codec accepts.
+const MAX_HEADERS: usize = 8192;
+
+/// What the `ContentLength` codec expects next in the input.
+#[derive(Clone, Debug)]
+enum Pending {
+    /// The headers, scanned for their end up to the position.
+    Headers(usize),
+    /// The body of the given length.
+    ///
+    /// If the headers were broken (but the length is known), the body is thrown away and the
+    /// broken message is produced instead.
+    Body(usize, Option<Broken>),
+    /// The rest of a body over the size limit, to be thrown away.
+    Skip(usize),
+}
+
+/// A codec working with JSONRPC 2.0 messages framed by headers.
+///
+/// This is the base protocol of the [Language Server
+/// Protocol](https://microsoft.github.io/language-server-protocol/). Each message is preceded by
+/// headers, each on its own line terminated by `\r\n`, and an empty line. The `Content-Length`
+/// header holds the size of the message in bytes and is mandatory, an optional `Content-Type` may
+/// only declare the `utf-8` charset. Other headers are ignored. The messages themselves may
+/// contain newlines. Encoding produces only the `Content-Length` header.
+///
+/// Broken headers are reported as a syntax error if the length of the message is still known, so
+/// the message can be skipped. A missing or invalid `Content-Length` or headers longer than 8 KiB
+/// are an I/O error, as the stream can't go on.
+#[derive(Clone, Debug)]
+pub struct ContentLength {
+    parser: Parser,
+    pending: Pending,
+}
+
+impl ContentLength {
+    /// A constructor
+    pub fn new() -> Self {
+        ContentLength::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        ContentLength {
+            parser,
+            pending: Pending::Headers(0),
+        }
+    }
+}
+
+impl Default for ContentLength {
+    fn default() -> Self {
+        ContentLength::with_parser(Parser::new())
+    }
+}
+
+/// Reads the headers and returns the length of the body.
+///
+/// The headers are split by `\n`, so they must be passed without the last `\n` and the empty
+/// line, but with the last `\r`.
+///
+/// Broken headers that still allow to find the end of the body are returned with it.
+fn parse_headers(headers: &[u8]) -> IoResult<(usize, Option<Broken>)> {
+    fn invalid(message: &str) -> Error {
+        Error::new(ErrorKind::InvalidData, message)
+    }
+
+    let mut length = None;
+    let mut broken = None;
+    for (number, line) in headers.split(|&b| b == b'\n').enumerate() {
+        let mut fail = |message: &str| {
+            broken.get_or_insert_with(|| {
+                Broken::SyntaxError(SyntaxError {
+                    message: message.to_owned(),
+                    line: number + 1,
+                    column: 1,
+                })
             });
-            (decoded, deserializer.byte_offset())
         };
+        let line = match line.split_last() {
+            Some((&b'\r', line)) => line,
+            _ => {
+                fail("Header line not terminated by CRLF");
+                line
+            },
+        };
+        let line = match ::std::str::from_utf8(line) {
+            Ok(line) if line.is_ascii() => line,
+            _ => {
+                fail("Header not in ASCII");
+                continue;
+            },
+        };
+        let (name, value) = match line.find(':') {
+            Some(colon) => (&line[..colon], line[colon + 1..].trim()),
+            None => {
+                fail("Header without a value");
+                continue;
+            },
+        };
+        if name.eq_ignore_ascii_case("Content-Length") {
+            if length.is_some() {
+                return Err(invalid("Duplicate Content-Length header"));
+            }
+            // The plain parse would also accept a leading +
+            if !value.bytes().all(|b| b.is_ascii_digit()) {
+                return Err(invalid("Invalid Content-Length header"));
+            }
+            let parsed = value
+                .parse()
+                .map_err(|_| invalid("Invalid Content-Length header"))?;
+            length = Some(parsed);
+        } else if name.eq_ignore_ascii_case("Content-Type") {
+            let charset = value
+                .split(';')
+                .skip(1)
+                .filter_map(|param| {
+                    let param = param.trim();
+                    let eq = param.find('=')?;
+                    if param[..eq].trim().eq_ignore_ascii_case("charset") {
+                        Some(param[eq + 1..].trim().trim_matches('"'))
+                    } else {
+                        None
+                    }
+                })
+                .next();
+            // The `utf8` is deprecated, but still accepted by the protocol
+            let supported = charset.into_iter().all(|charset| {
+                charset.eq_ignore_ascii_case("utf-8") || charset.eq_ignore_ascii_case("utf8")
+            });
+            if !supported {
+                fail("Unsupported charset");
+            }
+        }
+    }
+    length
+        .map(|length| (length, broken))
+        .ok_or_else(|| invalid("Missing Content-Length header"))
+}
+
+impl Encoder for ContentLength {
+    type Item = Message;
+    type Error = Error;
+    fn encode(&mut self, msg: Message, buf: &mut BytesMut) -> IoResult<()> {
+        let encoded = to_vec(&msg).map_err(err_map)?;
+        let headers = format!("Content-Length: {}\r\n\r\n", encoded.len());
+        buf.reserve(headers.len() + encoded.len());
+        buf.put_slice(headers.as_bytes());
+        buf.put_slice(&encoded);
+        Ok(())
+    }
+}
+
+impl Decoder for ContentLength {
+    type Item = Parsed;
+    type Error = Error;
+    fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
+        loop {
+            match self.pending {
+                Pending::Skip(ref mut len) => {
+                    skip_some(src, len);
+                    if *len > 0 {
+                        return Ok(None);
+                    }
+                    self.pending = Pending::Headers(0);
+                },
+                Pending::Headers(position) => {
+                    // The end may have started before the position, in the previous data
+                    let start = position.saturating_sub(3);
+                    let end = src[start..]
+                        .windows(4)
+                        .position(|window| window == b"\r\n\r\n");
+                    let end = match end {
+                        Some(end) => start + end,
+                        None if src.len() > MAX_HEADERS => {
+                            return Err(Error::new(ErrorKind::InvalidData, "Headers too large"));
+                        },
+                        None => {
+                            self.pending = Pending::Headers(src.len());
+                            return Ok(None);
+                        },
+                    };
+                    let headers = src.split_to(end + 4);
+                    let (len, broken) = parse_headers(&headers[..end + 1])?;
+                    if self.parser.size_exceeded(len) {
+                        self.pending = Pending::Skip(len);
+                        return check_fatal(&self.parser, Err(Broken::LimitExceeded(Limit::Size)))
+                            .map(Some);
+                    }
+                    self.pending = Pending::Body(len, broken);
+                },
+                Pending::Body(len, _) => {
+                    if src.len() < len {
+                        // Make room for (a part of) the rest of the message
+                        let missing = len - src.len();
+                        reserve_some(src, missing);
+                        return Ok(None);
+                    }
+                    let body = src.split_to(len);
+                    let pending = mem::replace(&mut self.pending, Pending::Headers(0));
+                    if let Pending::Body(_, Some(broken)) = pending {
+                        return Ok(Some(Err(broken)));
+                    }
+                    return check_fatal(&self.parser, self.parser.parse_slice(&body)).map(Some);
+                },
+            }
+        }
+    }
+}
+
+/// The framing picked by the [AutoDetect](struct.AutoDetect.html) codec.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Framing {
+    /// Newline-separated messages, as with the [Line](struct.Line.html) codec.
+    Line,
+    /// Messages one after another, as with the [Boundary](struct.Boundary.html) codec.
+    Boundary,
+    /// Messages preceded by headers, as with the [ContentLength](struct.ContentLength.html) codec.
+    ContentLength,
+}
+
+/// The codec picked by `AutoDetect`.
+#[derive(Clone, Debug)]
+enum Detected {
+    Line(Line),
+    Boundary(Boundary),
+    ContentLength(ContentLength),
+}
+
+/// A codec picking the framing by what the peer sends.
+///
+/// It looks at the start of the input. If it starts with a `Content-` header, the messages are
+/// framed by headers as with [ContentLength](struct.ContentLength.html). If the first message is
+/// followed by a newline (and contains none), the input is newline-separated as with
+/// [Line](struct.Line.html). Otherwise (eg. messages following each other on the same line, or a
+/// pretty-printed one), [Boundary](struct.Boundary.html) is used. The `Boundary` is also picked
+/// when the first message ends right at the end of the data received so far, as it handles
+/// newline-separated messages too. Input that doesn't look like JSON at all is handled as lines.
+/// Once picked, it works exactly like the chosen codec.
+///
+/// Until the framing is detected, the messages are encoded as newline-separated JSON (which is
+/// the same for `Line` and `Boundary`). Therefore it is best used on the side waiting for the peer
+/// to speak first, like a server.
+#[derive(Clone, Debug, Default)]
+pub struct AutoDetect {
+    parser: Parser,
+    scanner: Scanner,
+    detected: Option<Detected>,
+}
 
-        // It did read some data from the input. Find out how many and cut them off.
-        src.split_to(pos);
-        Ok(decoded)
+impl AutoDetect {
+    /// A constructor
+    pub fn new() -> Self {
+        AutoDetect::default()
+    }
+    /// A constructor with a custom-configured parser.
+    ///
+    /// The parser is passed to the chosen codec.
+    pub fn with_parser(parser: Parser) -> Self {
+        AutoDetect {
+            parser,
+            scanner: Scanner::default(),
+            detected: None,
+        }
+    }
+    /// The framing in use, if it was already detected.
+    pub fn framing(&self) -> Option<Framing> {
+        self.detected.as_ref().map(|detected| match *detected {
+            Detected::Line(_) => Framing::Line,
+            Detected::Boundary(_) => Framing::Boundary,
+            Detected::ContentLength(_) => Framing::ContentLength,
+        })
+    }
+    /// Picks the framing by the start of the input, if it can already tell.
+    fn detect(&mut self, buf: &[u8]) -> Option<Framing> {
+        const HEADER: &[u8] = b"content-";
+        let prefix = cmp::min(buf.len(), HEADER.len());
+        if buf[..prefix].eq_ignore_ascii_case(&HEADER[..prefix]) {
+            return if prefix == HEADER.len() {
+                Some(Framing::ContentLength)
+            } else {
+                None
+            };
+        }
+        match self.scanner.scan(buf) {
+            Scanned::Incomplete => None,
+            Scanned::Other => Some(Framing::Line),
+            Scanned::Complete(end) => {
+                let start = self.scanner.start.unwrap_or(0);
+                let multiline = buf[start..end].contains(&b'\n');
+                let next = buf[end..]
+                    .iter()
+                    .find(|&&b| b != b' ' && b != b'\t' && b != b'\r');
+                if !multiline && next == Some(&b'\n') {
+                    Some(Framing::Line)
+                } else {
+                    Some(Framing::Boundary)
+                }
+            },
+        }
+    }
+}
+
+impl Encoder for AutoDetect {
+    type Item = Message;
+    type Error = Error;
+    fn encode(&mut self, msg: Message, buf: &mut BytesMut) -> IoResult<()> {
+        match self.detected {
+            Some(Detected::ContentLength(ref mut codec)) => codec.encode(msg, buf),
+            _ => encode_codec(&msg, buf),
+        }
+    }
+}
+
+impl Decoder for AutoDetect {
+    type Item = Parsed;
+    type Error = Error;
+    fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
+        if self.detected.is_none() {
+            let framing = match self.detect(src) {
+                Some(framing) => framing,
+                // Too large to tell, the lines at least can skip it
+                None if self.parser.size_exceeded(src.len()) => Framing::Line,
+                None => return Ok(None),
+            };
+            let parser = self.parser.clone();
+            self.detected = Some(match framing {
+                Framing::Line => Detected::Line(Line::with_parser(parser)),
+                Framing::Boundary => Detected::Boundary(Boundary::with_parser(parser)),
+                Framing::ContentLength => {
+                    Detected::ContentLength(ContentLength::with_parser(parser))
+                },
+            });
+        }
+        match self.detected {
+            Some(Detected::Line(ref mut codec)) => codec.decode(src),
+            Some(Detected::Boundary(ref mut codec)) => codec.decode(src),
+            Some(Detected::ContentLength(ref mut codec)) => codec.decode(src),
+            None => unreachable!("Detected above"),
+        }
+    }
+}
+
+/// The size of the length prefix of the `LengthPrefixed` and the binary codecs.
+const PREFIX_LEN: usize = 4;
+
+/// Appends an encoded message to the buffer, prefixed by its length.
+///
+/// The length is a 32-bit big endian number.
+fn encode_prefixed(encoded: &[u8], buf: &mut BytesMut) -> IoResult<()> {
+    if encoded.len() > u32::MAX as usize {
+        return Err(Error::new(ErrorKind::InvalidInput, "Message too large"));
+    }
+    buf.reserve(PREFIX_LEN + encoded.len());
+    buf.put_u32_be(encoded.len() as u32);
+    buf.put_slice(encoded);
+    Ok(())
+}
+
+/// Cuts a length-prefixed message out of the buffer, if it is already complete.
+///
+/// A message over the size limit is not buffered, it is skipped as it comes. The `skip` holds how
+/// much of it is still to be thrown away.
+fn decode_prefixed(buf: &mut BytesMut, parser: &Parser, skip: &mut usize) -> Option<Frame> {
+    skip_some(buf, skip);
+    if *skip > 0 || buf.len() < PREFIX_LEN {
+        return None;
+    }
+    let mut prefix = [0; PREFIX_LEN];
+    prefix.copy_from_slice(&buf[..PREFIX_LEN]);
+    let len = u32::from_be_bytes(prefix) as usize;
+    if parser.size_exceeded(len) {
+        buf.split_to(PREFIX_LEN);
+        *skip = len;
+        skip_some(buf, skip);
+        return Some(Frame::TooLarge);
+    }
+    if buf.len() < PREFIX_LEN + len {
+        // Make room for (a part of) the rest of the message
+        let missing = PREFIX_LEN + len - buf.len();
+        reserve_some(buf, missing);
+        return None;
+    }
+    buf.split_to(PREFIX_LEN);
+    Some(Frame::Data(buf.split_to(len)))
+}
+
+/// A codec working with JSONRPC 2.0 messages prefixed by their length.
+///
+/// This produces or encodes [Message](../message/enum.Message.html) in JSON, each prefixed by its
+/// length in bytes (32 bits, big endian). Unlike the separated or boundary codecs, the end of the
+/// message is known from the start, without scanning the data, and the message may contain any
+/// bytes.
+#[derive(Clone, Debug, Default)]
+pub struct LengthPrefixed {
+    parser: Parser,
+    skip: usize,
+}
+
+impl LengthPrefixed {
+    /// A constructor
+    pub fn new() -> Self {
+        LengthPrefixed::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        LengthPrefixed { parser, skip: 0 }
+    }
+}
+
+impl Encoder for LengthPrefixed {
+    type Item = Message;
+    type Error = Error;
+    fn encode(&mut self, msg: Message, buf: &mut BytesMut) -> IoResult<()> {
+        let encoded = to_vec(&msg).map_err(err_map)?;
+        encode_prefixed(&encoded, buf)
+    }
+}
+
+impl Decoder for LengthPrefixed {
+    type Item = Parsed;
+    type Error = Error;
+    fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
+        let frame = decode_prefixed(src, &self.parser, &mut self.skip);
+        parse_frame(&self.parser, frame, |frame| self.parser.parse_slice(&frame))
+    }
+}
+
+/// The longest length of a netstring accepted, in digits.
+const NETSTRING_DIGITS: usize = 20;
+
+/// A codec working with JSONRPC 2.0 messages encoded as netstrings.
+///
+/// Each message is in JSON, framed as a [netstring](https://cr.yp.to/proto/netstrings.txt) ‒ its
+/// length in bytes as a decimal number, a colon, the message and a comma (eg. `2:[],`). Like with
+/// [LengthPrefixed](struct.LengthPrefixed.html), the end of the message is known in advance.
+///
+/// A frame with an invalid length or without the final comma is an I/O error, as the stream can't
+/// go on after it.
+#[derive(Clone, Debug, Default)]
+pub struct Netstring {
+    parser: Parser,
+    skip: usize,
+}
+
+impl Netstring {
+    /// A constructor
+    pub fn new() -> Self {
+        Netstring::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        Netstring { parser, skip: 0 }
+    }
+    /// Cuts a netstring out of the buffer, if it is already complete.
+    fn frame(&mut self, buf: &mut BytesMut) -> IoResult<Option<Frame>> {
+        fn invalid(message: &str) -> Error {
+            Error::new(ErrorKind::InvalidData, message)
+        }
+
+        skip_some(buf, &mut self.skip);
+        if self.skip > 0 {
+            return Ok(None);
+        }
+        let digits = buf.iter()
+            .take(NETSTRING_DIGITS + 1)
+            .take_while(|byte| byte.is_ascii_digit())
+            .count();
+        if digits > NETSTRING_DIGITS {
+            return Err(invalid("Netstring length too long"));
+        }
+        match buf.get(digits) {
+            None => return Ok(None),
+            Some(&b':') if digits > 0 => (),
+            Some(_) => return Err(invalid("Invalid netstring length")),
+        }
+        let len = ::std::str::from_utf8(&buf[..digits])
+            .expect("Digits are ASCII")
+            .parse::<usize>()
+            .map_err(|_| invalid("Netstring length too long"))?;
+        let header = digits + 1;
+        // The header, the message and the comma
+        let total = len
+            .checked_add(header + 1)
+            .ok_or_else(|| invalid("Netstring length too long"))?;
+        if self.parser.size_exceeded(len) {
+            // Skip the comma too, without checking it
+            buf.split_to(header);
+            self.skip = total - header;
+            skip_some(buf, &mut self.skip);
+            return Ok(Some(Frame::TooLarge));
+        }
+        if buf.len() < total {
+            // Make room for (a part of) the rest of the message
+            let missing = total - buf.len();
+            reserve_some(buf, missing);
+            return Ok(None);
+        }
+        if buf[total - 1] != b',' {
+            return Err(invalid("Netstring not terminated by a comma"));
+        }
+        buf.split_to(header);
+        let data = buf.split_to(len);
+        buf.split_to(1);
+        Ok(Some(Frame::Data(data)))
+    }
+}
+
+impl Encoder for Netstring {
+    type Item = Message;
+    type Error = Error;
+    fn encode(&mut self, msg: Message, buf: &mut BytesMut) -> IoResult<()> {
+        let encoded = to_vec(&msg).map_err(err_map)?;
+        let header = format!("{}:", encoded.len());
+        buf.reserve(header.len() + encoded.len() + 1);
+        buf.put_slice(header.as_bytes());
+        buf.put_slice(&encoded);
+        buf.put(b',');
+        Ok(())
+    }
+}
+
+impl Decoder for Netstring {
+    type Item = Parsed;
+    type Error = Error;
+    fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
+        let frame = self.frame(src)?;
+        parse_frame(&self.parser, frame, |frame| self.parser.parse_slice(&frame))
+    }
+}
+
+/// Describes an undecodable binary message.
+///
+/// There are no lines in the binary formats, so there's no position.
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+fn binary_error<E: Display>(e: E) -> SyntaxError {
+    SyntaxError {
+        message: format!("{}", e),
+        line: 0,
+        column: 0,
+    }
+}
+
+/// A codec working with JSONRPC 2.0 messages encoded in CBOR.
+///
+/// This produces or encodes [Message](../message/enum.Message.html), like the JSON codecs, but
+/// each of them is encoded in [CBOR](https://cbor.io) and prefixed by its length (32 bits, big
+/// endian). A message that can't be decoded is reported as a syntax error and the stream goes on
+/// with the next one.
+///
+/// Available with the `cbor` feature.
+#[cfg(feature = "cbor")]
+#[derive(Clone, Debug, Default)]
+pub struct Cbor {
+    parser: Parser,
+    skip: usize,
+}
+
+#[cfg(feature = "cbor")]
+impl Cbor {
+    /// A constructor
+    pub fn new() -> Self {
+        Cbor::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        Cbor { parser, skip: 0 }
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl Encoder for Cbor {
+    type Item = Message;
+    type Error = Error;
+    fn encode(&mut self, msg: Message, buf: &mut BytesMut) -> IoResult<()> {
+        let encoded = ::serde_cbor::to_vec(&msg).map_err(err_map)?;
+        encode_prefixed(&encoded, buf)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl Decoder for Cbor {
+    type Item = Parsed;
+    type Error = Error;
+    fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
+        let frame = decode_prefixed(src, &self.parser, &mut self.skip);
+        parse_frame(&self.parser, frame, |frame| {
+            let decoded = ::serde_cbor::from_slice(&frame).map_err(binary_error);
+            self.parser.decoded_to_parsed(decoded)
+        })
+    }
+}
+
+/// A codec working with JSONRPC 2.0 messages encoded in MessagePack.
+///
+/// This works like the [Cbor](struct.Cbor.html) codec, only the messages are encoded in
+/// [MessagePack](https://msgpack.org). Structures are encoded as maps with the field names, so the
+/// messages have the same shape as in JSON.
+///
+/// Available with the `msgpack` feature.
+#[cfg(feature = "msgpack")]
+#[derive(Clone, Debug, Default)]
+pub struct MessagePack {
+    parser: Parser,
+    skip: usize,
+}
+
+#[cfg(feature = "msgpack")]
+impl MessagePack {
+    /// A constructor
+    pub fn new() -> Self {
+        MessagePack::default()
+    }
+    /// A constructor with a custom-configured parser.
+    pub fn with_parser(parser: Parser) -> Self {
+        MessagePack { parser, skip: 0 }
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl Encoder for MessagePack {
+    type Item = Message;
+    type Error = Error;
+    fn encode(&mut self, msg: Message, buf: &mut BytesMut) -> IoResult<()> {
+        let encoded = ::rmp_serde::to_vec_named(&msg).map_err(err_map)?;
+        encode_prefixed(&encoded, buf)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl Decoder for MessagePack {
+    type Item = Parsed;
+    type Error = Error;
+    fn decode(&mut self, src: &mut BytesMut) -> IoResult<Option<Parsed>> {
+        let frame = decode_prefixed(src, &self.parser, &mut self.skip);
+        parse_frame(&self.parser, frame, |frame| {
+            let decoded = ::rmp_serde::from_slice(&frame).map_err(binary_error);
+            self.parser.decoded_to_parsed(decoded)
+        })
+    }
+}
+
+/// A connection built from separate reading and writing halves.
+///
+/// The [`Endpoint`](../endpoint/struct.Endpoint.html) needs a single connection that both produces
+/// the received messages (parsed or [raw](struct.RawLine.html)) and accepts the ones to send. This
+/// glues a reader and a writer together, each framed with its own copy of the codec. This is handy
+/// when the two directions don't share the same object, like with pipes or stdin and stdout of a
+/// process.
+#[allow(deprecated)]
+pub struct Halves<Reader, Writer, Codec> {
+    read: FramedRead<Reader, Codec>,
+    write: FramedWrite<Writer, Codec>,
+}
+
+impl<Reader, Writer, Codec> Halves<Reader, Writer, Codec>
+where
+    Reader: AsyncRead,
+    Writer: AsyncWrite,
+    Codec: Decoder + Encoder + Clone,
+{
+    /// A constructor.
+    #[allow(deprecated)]
+    pub fn new(reader: Reader, writer: Writer, codec: Codec) -> Self {
+        Halves {
+            read: FramedRead::new(reader, codec.clone()),
+            write: FramedWrite::new(writer, codec),
+        }
+    }
+    /// Unwraps the halves and provides the reader and writer back.
+    ///
+    /// Note that any data buffered inside is lost.
+    pub fn into_inner(self) -> (Reader, Writer) {
+        (self.read.into_inner(), self.write.into_inner())
+    }
+}
+
+impl<Reader, Writer, Codec> Stream for Halves<Reader, Writer, Codec>
+where
+    Reader: AsyncRead,
+    Codec: Decoder<Error = Error>,
+    <Codec as Decoder>::Item: Into<Received>,
+{
+    type Item = <Codec as Decoder>::Item;
+    type Error = Error;
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Error> {
+        self.read.poll()
+    }
+}
+
+impl<Reader, Writer, Codec> Sink for Halves<Reader, Writer, Codec>
+where
+    Writer: AsyncWrite,
+    Codec: Encoder<Item = Message, Error = Error>,
+{
+    type SinkItem = Message;
+    type SinkError = Error;
+    fn start_send(&mut self, msg: Message) -> StartSend<Message, Error> {
+        self.write.start_send(msg)
+    }
+    fn poll_complete(&mut self) -> Poll<(), Error> {
+        self.write.poll_complete()
+    }
+    fn close(&mut self) -> Poll<(), Error> {
+        self.write.close()
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use message::Broken;
+    use std::convert::TryFrom;
+    use serde_json::Value;
+    use message::{from_str, Broken, Limit, MessageRef, Params, RpcError};
 
     #[test]
     fn encode() {
@@ -268,11 +1337,33 @@ mod tests {
         );
     }
 
+    /// The raw frames are cut out unparsed and can be parsed into borrowed messages.
+    #[test]
+    fn decode_raw() {
+        let mut codec = RawLine::new();
+        let mut buf = get_buf(b"{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}\n{]\n{\"jsonrpc");
+        let frame = codec.decode(&mut buf).unwrap().unwrap();
+        assert_eq!(&b"{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}"[..], &frame[..]);
+        match Parser::new().parse_ref(&frame).unwrap() {
+            MessageRef::Notification(ref notif) => assert_eq!("notif", notif.method),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+        // Broken frames are cut out as well, the parsing reports them
+        let frame = codec.decode(&mut buf).unwrap().unwrap();
+        match Parser::new().parse_ref(&frame) {
+            Err(Broken::SyntaxError(_)) => (),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+        // The incomplete one stays in
+        assert!(codec.decode(&mut buf).unwrap().is_none());
+        assert_eq!(&b"{\"jsonrpc"[..], &buf[..]);
+    }
+
     /// Not enough data for a whole message
     #[test]
     fn decode_boundary_short() {
         let mut buf = get_buf(b"{\"jsonrpc\":\"");
-        assert!(Boundary.decode(&mut buf).unwrap().is_none());
+        assert!(Boundary::new().decode(&mut buf).unwrap().is_none());
         assert_eq!(&buf, &b"{\"jsonrpc\":\""[..]);
     }
 
@@ -283,9 +1374,511 @@ mod tests {
     fn decode_boundary_prefix() {
         let mut buf = get_buf(b"\n\n {\"jsonrpc\":\"2.0\",\"method\":\"notif\"}{\"");
         assert_eq!(
-            Boundary.decode(&mut buf).unwrap().unwrap(),
+            Boundary::new().decode(&mut buf).unwrap().unwrap(),
             Ok(Message::notification("notif".to_owned(), None))
         );
         assert_eq!(&buf, &b"{\""[..]);
     }
+
+    /// The line codecs don't buffer more than the size limit and go on after a too large line.
+    #[test]
+    fn decode_limits() {
+        fn check<C: Decoder<Item = Parsed, Error = Error>>(mut codec: C) {
+            let notif = Message::notification("notif".to_owned(), None);
+            let mut buf = get_buf(b"{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}\n");
+            assert_eq!(Some(Ok(notif.clone())), codec.decode(&mut buf).unwrap());
+            // A complete line that is too large
+            let mut buf = get_buf(b"[                                         ]\n");
+            assert_eq!(
+                Some(Err(Broken::LimitExceeded(Limit::Size))),
+                codec.decode(&mut buf).unwrap()
+            );
+            assert!(buf.is_empty());
+            // A line reported as too large before it ends, the rest of it is thrown away
+            buf.extend_from_slice(&[b' '; 30]);
+            assert_eq!(None, codec.decode(&mut buf).unwrap());
+            buf.extend_from_slice(&[b' '; 30]);
+            assert_eq!(
+                Some(Err(Broken::LimitExceeded(Limit::Size))),
+                codec.decode(&mut buf).unwrap()
+            );
+            assert!(buf.is_empty());
+            buf.extend_from_slice(&[b' '; 30]);
+            assert_eq!(None, codec.decode(&mut buf).unwrap());
+            assert!(buf.is_empty());
+            buf.extend_from_slice(b"   ]\n{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}\n");
+            assert_eq!(Some(Ok(notif)), codec.decode(&mut buf).unwrap());
+            // Too deep lines are thrown away too
+            let mut buf = get_buf(b"[[[[[[]]]]]]\n");
+            assert_eq!(
+                Some(Err(Broken::LimitExceeded(Limit::Depth))),
+                codec.decode(&mut buf).unwrap()
+            );
+        }
+
+        let parser = Parser::new().max_size(Some(40)).max_depth(Some(3));
+        check(Line::with_parser(parser.clone()));
+        check(DirtyLine::with_parser(parser.clone()));
+
+        // With fatal limits, it is an error
+        let fatal = parser.clone().fatal_limits(true);
+        let mut buf = get_buf(&[b' '; 50]);
+        let err = Line::with_parser(fatal.clone())
+            .decode(&mut buf)
+            .unwrap_err();
+        assert_eq!(ErrorKind::InvalidData, err.kind());
+        let mut buf = get_buf(b"[[[[[[]]]]]]\n");
+        assert!(Line::with_parser(fatal.clone()).decode(&mut buf).is_err());
+        // The raw codec has no other way to report it than an error
+        let mut buf = get_buf(&[b' '; 50]);
+        assert!(RawLine::with_parser(parser).decode(&mut buf).is_err());
+    }
+
+    /// The boundary codec scans the data only once, however small the pieces are.
+    #[test]
+    fn decode_boundary_incremental() {
+        let msg = br#" {"jsonrpc": "2.0", "method": "n}]\"\\", "params": [{"a": ["[\\\"{"]}]}"#;
+        let expected = Message::notification(
+            "n}]\"\\".to_owned(),
+            Params::try_from(json!([{"a": ["[\\\"{"]}])).ok(),
+        );
+        let mut codec = Boundary::new();
+        let mut buf = BytesMut::new();
+        for (i, byte) in msg.iter().enumerate() {
+            buf.extend_from_slice(&[*byte]);
+            if i + 1 < msg.len() {
+                assert_eq!(None, codec.decode(&mut buf).unwrap());
+                // Nothing gets parsed or scanned twice before the message is complete
+                assert_eq!(buf.len(), codec.scanner.position);
+            }
+        }
+        assert_eq!(Some(Ok(expected)), codec.decode(&mut buf).unwrap());
+        assert!(buf.is_empty());
+        assert_eq!(0, codec.scanner.position);
+
+        // Whitespace is not kept around
+        let mut buf = get_buf(b"  \n \r\n\t");
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        assert!(buf.is_empty());
+
+        // Other values than objects still get through (and are not valid messages)
+        let mut buf = get_buf(b"\"[{\" 42 [] ");
+        for value in &[json!("[{"), json!(42), json!([])] {
+            assert_eq!(
+                Some(Err(Broken::Unmatched(value.clone()))),
+                codec.decode(&mut buf).unwrap()
+            );
+        }
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+    }
+
+    /// After garbage, the boundary codec finds the next message and reports the garbage once.
+    #[test]
+    fn decode_boundary_resync() {
+        let notif = Message::notification("notif".to_owned(), None);
+        let msg = "{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}";
+        let decode_all = |codec: &mut Boundary, buf: &mut BytesMut| {
+            let mut decoded = Vec::new();
+            while let Some(parsed) = codec.decode(buf).unwrap() {
+                decoded.push(parsed.map_err(|broken| match broken {
+                    Broken::SyntaxError(_) => (),
+                    other => panic!("Unexpected broken message {:?}", other),
+                }));
+            }
+            decoded
+        };
+
+        let mut codec = Boundary::new();
+        // Garbage in front, including things that look like a start of a message
+        let mut buf = get_buf(format!("xyz {{q [}} {}{}", msg, msg).as_bytes());
+        assert_eq!(
+            vec![Err(()), Ok(notif.clone()), Ok(notif.clone())],
+            decode_all(&mut codec, &mut buf)
+        );
+        assert!(buf.is_empty());
+        // Each damaged region is reported on its own
+        let mut buf = get_buf(format!("@{}{{]{}", msg, msg).as_bytes());
+        assert_eq!(
+            vec![Err(()), Ok(notif.clone()), Err(()), Ok(notif.clone())],
+            decode_all(&mut codec, &mut buf)
+        );
+        // The region may span more reads
+        let mut buf = get_buf(b"\x00\xff");
+        assert_eq!(vec![Err(())], decode_all(&mut codec, &mut buf));
+        buf.extend_from_slice(b"{{ ]");
+        assert!(decode_all(&mut codec, &mut buf).is_empty());
+        buf.extend_from_slice(msg.as_bytes());
+        assert_eq!(vec![Ok(notif)], decode_all(&mut codec, &mut buf));
+        assert!(buf.is_empty());
+    }
+
+    /// The boundary codec can't skip an incomplete message, but a complete one is only reported.
+    #[test]
+    fn decode_boundary_limits() {
+        let parser = Parser::new().max_size(Some(40)).max_depth(Some(3));
+        let mut codec = Boundary::with_parser(parser.clone());
+        let mut buf = get_buf(b"[                                         ] [[[[]]]] [");
+        assert_eq!(
+            Some(Err(Broken::LimitExceeded(Limit::Size))),
+            codec.decode(&mut buf).unwrap()
+        );
+        assert_eq!(
+            Some(Err(Broken::LimitExceeded(Limit::Depth))),
+            codec.decode(&mut buf).unwrap()
+        );
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        buf.extend_from_slice(&[b' '; 50]);
+        assert!(codec.decode(&mut buf).is_err());
+
+        let mut codec = Boundary::with_parser(parser.fatal_limits(true));
+        let mut buf = get_buf(b"[[[[]]]]");
+        assert!(codec.decode(&mut buf).is_err());
+    }
+
+    /// The messages framed by headers can contain newlines and arrive in pieces.
+    #[test]
+    fn content_length() {
+        let mut codec = ContentLength::new();
+        let notif = Message::notification("notif".to_owned(), None);
+        let mut buf = BytesMut::new();
+        codec.encode(notif.clone(), &mut buf).unwrap();
+        assert_eq!(
+            &b"Content-Length: 34\r\n\r\n{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}"[..],
+            &buf[..]
+        );
+        let body = "{\r\n  \"jsonrpc\": \"2.0\",\n  \"method\": \"notif\"\n}";
+        let input = format!(
+            "Content-Length: {}\r\n\
+             Content-Type: application/vscode-jsonrpc; charset=utf-8\r\n\r\n{}\
+             content-length:{}\r\n\r\n{}",
+            body.len(),
+            body,
+            body.len(),
+            body
+        );
+        // Feed it byte by byte, the messages come out once complete
+        let mut buf = BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in input.bytes() {
+            buf.extend_from_slice(&[byte]);
+            if let Some(parsed) = codec.decode(&mut buf).unwrap() {
+                decoded.push(parsed);
+            }
+        }
+        assert_eq!(vec![Ok(notif.clone()), Ok(notif.clone())], decoded);
+        assert!(buf.is_empty());
+    }
+
+    /// Broken headers are skipped together with their message, if possible.
+    #[test]
+    fn content_length_broken() {
+        let notif = Message::notification("notif".to_owned(), None);
+        let mut codec = ContentLength::new();
+        let mut buf = get_buf(
+            b"Content-Length: 2\r\nContent-Type: text/plain; charset=latin2\r\n\r\n[]\
+              Content-Length: 2\r\nGarbage\r\n\r\n[]\
+              Content-Length: 2\r\nX-Name: \xc3\xbc\r\n\r\n[]\
+              Content-Length: 3\r\n\r\n{]\n\
+              Content-Length: 34\r\n\r\n{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}",
+        );
+        let mut broken = |line| match codec.decode(&mut buf).unwrap() {
+            Some(Err(Broken::SyntaxError(ref e))) => assert_eq!(line, e.line),
+            other => panic!("Something unexpected: {:?}", other),
+        };
+        broken(2);
+        broken(2);
+        broken(2);
+        broken(1);
+        assert_eq!(Some(Ok(notif)), codec.decode(&mut buf).unwrap());
+        assert!(buf.is_empty());
+
+        // Without the length, it is not possible to go on
+        for input in &[
+            &b"Content-Type: application/vscode-jsonrpc\r\n\r\n"[..],
+            &b"Content-Length: many\r\n\r\n"[..],
+            &b"Content-Length: +2\r\n\r\n[]"[..],
+            &b"Content-Length: 2\r\nContent-Length: 2\r\n\r\n"[..],
+            &[b'x'; MAX_HEADERS + 1][..],
+        ] {
+            assert!(ContentLength::new().decode(&mut get_buf(input)).is_err());
+        }
+
+        // Too large messages are skipped
+        let parser = Parser::new().max_size(Some(10));
+        let mut codec = ContentLength::with_parser(parser.clone());
+        let mut buf = get_buf(b"Content-Length: 12\r\n\r\n[   ");
+        assert_eq!(
+            Some(Err(Broken::LimitExceeded(Limit::Size))),
+            codec.decode(&mut buf).unwrap()
+        );
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        buf.extend_from_slice(b"       ]Content-Length: 2\r\n\r\n[]");
+        match codec.decode(&mut buf).unwrap() {
+            Some(Err(Broken::Unmatched(_))) => (),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+        let mut buf = get_buf(b"Content-Length: 12\r\n\r\n");
+        assert!(ContentLength::with_parser(parser.fatal_limits(true))
+            .decode(&mut buf)
+            .is_err());
+
+        // A huge length doesn't allocate the whole message in advance
+        let mut codec = ContentLength::new();
+        let mut buf = get_buf(b"Content-Length: 18446744073709551615\r\n\r\n[");
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        assert!(buf.capacity() <= 2 * MAX_RESERVE);
+        buf.extend_from_slice(&[b' '; 1000]);
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+    }
+
+    /// The auto-detecting codec picks the framing by the first data and then decodes with it.
+    #[test]
+    fn auto_detect() {
+        let notif = Message::notification("notif".to_owned(), None);
+        let msg = "{\"jsonrpc\":\"2.0\",\"method\":\"notif\"}";
+        let pretty = "{\n  \"jsonrpc\": \"2.0\",\n  \"method\": \"notif\"\n}";
+        // Feeds the input in pieces of given size, so it may have to wait for enough data to
+        // decide
+        let check = |input: String, piece: usize, framing: Framing| {
+            let mut codec = AutoDetect::new();
+            let mut buf = BytesMut::new();
+            let mut decoded = Vec::new();
+            for piece in input.as_bytes().chunks(piece) {
+                buf.extend_from_slice(piece);
+                while let Some(parsed) = codec.decode(&mut buf).unwrap() {
+                    decoded.push(parsed);
+                }
+            }
+            assert_eq!(Some(framing), codec.framing(), "{}", input);
+            assert_eq!(vec![Ok(notif.clone()), Ok(notif.clone())], decoded);
+            assert!(buf.is_empty());
+            codec
+        };
+
+        let mut codec = check(
+            format!("Content-Length: 34\r\n\r\n{}Content-Length: 34\r\n\r\n{}", msg, msg),
+            1,
+            Framing::ContentLength,
+        );
+        // It answers in the same framing
+        let mut output = BytesMut::new();
+        codec.encode(notif.clone(), &mut output).unwrap();
+        assert!(output.starts_with(b"Content-Length: 34\r\n\r\n"));
+        check(
+            format!("content-type: application/vscode-jsonrpc\r\ncontent-length: 34\r\n\r\n{}\
+                     Content-Length: 34\r\n\r\n{}", msg, msg),
+            3,
+            Framing::ContentLength,
+        );
+        check(format!("{}\n{}\n", msg, msg), 50, Framing::Line);
+        check(format!("  {} \r\n{}\r\n", msg, msg), 50, Framing::Line);
+        check(format!("{}{}", msg, msg), 50, Framing::Boundary);
+        check(format!("{}  {}", msg, msg), 50, Framing::Boundary);
+        check(format!("{}\n{}", pretty, pretty), 5, Framing::Boundary);
+        // If the message ends with the data, it's not known if a newline follows
+        check(format!("{}\n{}\n", msg, msg), 34, Framing::Boundary);
+
+        // Something completely different is handled by lines
+        let mut codec = AutoDetect::new();
+        let mut buf = get_buf(b"GET / HTTP/1.1\r\n");
+        match codec.decode(&mut buf).unwrap() {
+            Some(Err(Broken::SyntaxError(_))) => (),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+        assert_eq!(Some(Framing::Line), codec.framing());
+
+        // Nothing is decided before there's enough data
+        let mut codec = AutoDetect::new();
+        let mut buf = get_buf(b" \n {\"jsonrpc\"");
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        assert_eq!(None, codec.framing());
+        let mut codec = AutoDetect::new();
+        let mut buf = get_buf(b"Conte");
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        assert_eq!(None, codec.framing());
+        // Before deciding, it encodes lines
+        let mut output = BytesMut::new();
+        codec.encode(notif, &mut output).unwrap();
+        assert_eq!(format!("{}\n", msg).as_bytes(), &output[..]);
+    }
+
+    /// Checks a length-prefixed codec encodes and decodes messages.
+    ///
+    /// The `encode` converts a JSON value into the format, to check handling of invalid messages.
+    /// The `line` is where the syntax errors are reported.
+    fn prefixed<C, E>(mut codec: C, encode: E, line: usize)
+    where
+        C: Decoder<Item = Parsed, Error = Error> + Encoder<Item = Message, Error = Error>,
+        E: Fn(&Value) -> Vec<u8>,
+    {
+        let messages = vec![
+            Message::request("call".to_owned(), Params::try_from(json!([1, "x", -2.5])).ok()),
+            Message::notification("notif".to_owned(), Params::try_from(json!({"x": null})).ok()),
+            from_str(r#"{"jsonrpc": "2.0", "result": {"x": [true, null]}, "id": -3}"#).unwrap(),
+            Message::error(RpcError::invalid_params(Some("Nope".to_owned()))),
+            Message::Batch(vec![
+                Message::notification("notif".to_owned(), None),
+                Message::notification("notif".to_owned(), None),
+            ]),
+        ];
+        let mut buf = BytesMut::new();
+        for msg in &messages {
+            codec.encode(msg.clone(), &mut buf).unwrap();
+        }
+        // Feed the data in small pieces, the messages come out only once complete
+        let mut input = BytesMut::new();
+        let mut decoded = Vec::new();
+        while !buf.is_empty() {
+            let len = ::std::cmp::min(3, buf.len());
+            input.extend_from_slice(&buf.split_to(len));
+            while let Some(msg) = codec.decode(&mut input).unwrap() {
+                decoded.push(msg.unwrap());
+            }
+        }
+        assert_eq!(messages, decoded);
+        assert!(input.is_empty());
+
+        // Something that is not a message and something that is not the format at all
+        let mut input = BytesMut::new();
+        let unmatched = json!({"jsonrpc": "2.0", "x": 1});
+        let garbage = [0xc1, 0xff, 0xff];
+        for frame in &[&encode(&unmatched)[..], &garbage[..]] {
+            input.put_u32_be(frame.len() as u32);
+            input.extend_from_slice(frame);
+        }
+        assert_eq!(
+            Some(Err(Broken::Unmatched(unmatched))),
+            codec.decode(&mut input).unwrap()
+        );
+        match codec.decode(&mut input).unwrap() {
+            Some(Err(Broken::SyntaxError(ref e))) => assert_eq!(line, e.line),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+        assert!(input.is_empty());
+    }
+
+    /// Checks a length-prefixed codec skips frames over the size limit.
+    ///
+    /// The codec must be configured with the size limit of 30.
+    fn prefixed_limits<C>(mut codec: C)
+    where
+        C: Decoder<Item = Parsed, Error = Error> + Encoder<Item = Message, Error = Error>,
+    {
+        // The prefix says the frame is huge, but only a part of it arrives at once
+        let mut input = BytesMut::new();
+        input.put_u32_be(50);
+        input.extend_from_slice(&[0; 20]);
+        assert_eq!(
+            Some(Err(Broken::LimitExceeded(Limit::Size))),
+            codec.decode(&mut input).unwrap()
+        );
+        assert!(input.is_empty());
+        input.extend_from_slice(&[0; 20]);
+        assert_eq!(None, codec.decode(&mut input).unwrap());
+        assert!(input.is_empty());
+        // The rest of the large one and a small one after it
+        let msg = Message::notification("x".to_owned(), None);
+        input.extend_from_slice(&[0; 10]);
+        let mut encoded = BytesMut::new();
+        codec.encode(msg.clone(), &mut encoded).unwrap();
+        assert!(encoded.len() - PREFIX_LEN <= 30, "Can't test with too large message");
+        input.extend_from_slice(&encoded);
+        assert_eq!(Some(Ok(msg)), codec.decode(&mut input).unwrap());
+        assert!(input.is_empty());
+    }
+
+    #[test]
+    fn length_prefixed() {
+        prefixed(
+            LengthPrefixed::new(),
+            |value| ::serde_json::to_vec(value).unwrap(),
+            1,
+        );
+        prefixed_limits(LengthPrefixed::with_parser(Parser::new().max_size(Some(30))));
+
+        // A huge length doesn't allocate the whole message in advance
+        let mut input = BytesMut::new();
+        input.put_u32_be(u32::MAX);
+        input.put(b'[');
+        assert_eq!(None, LengthPrefixed::new().decode(&mut input).unwrap());
+        assert!(input.capacity() <= 2 * MAX_RESERVE);
+    }
+
+    #[test]
+    fn netstring() {
+        let mut codec = Netstring::new();
+        let notif = Message::notification("notif".to_owned(), None);
+        let mut buf = BytesMut::new();
+        codec.encode(notif.clone(), &mut buf).unwrap();
+        assert_eq!(
+            &b"34:{\"jsonrpc\":\"2.0\",\"method\":\"notif\"},"[..],
+            &buf[..]
+        );
+        // Newlines are fine inside, the messages arrive in pieces
+        let input = b"36:{\"jsonrpc\":\"2.0\",\n\"method\":\"notif\"\n},2:[],";
+        let mut buf = BytesMut::new();
+        let mut decoded = Vec::new();
+        for byte in input.iter() {
+            buf.extend_from_slice(&[*byte]);
+            if let Some(parsed) = codec.decode(&mut buf).unwrap() {
+                decoded.push(parsed);
+            }
+        }
+        assert_eq!(2, decoded.len());
+        assert_eq!(Ok(notif), decoded[0]);
+        match decoded[1] {
+            Err(Broken::Unmatched(_)) => (),
+            ref other => panic!("Something unexpected: {:?}", other),
+        }
+        assert!(buf.is_empty());
+
+        // Broken framing can't be recovered from
+        for input in &[
+            &b":[],"[..],
+            &b"x:[],"[..],
+            &b"2:[]]"[..],
+            &b"99999999999999999999:"[..],
+            &b"999999999999999999999"[..],
+        ] {
+            assert!(Netstring::new().decode(&mut get_buf(input)).is_err());
+        }
+
+        // Too large messages are skipped, including the comma
+        let mut codec = Netstring::with_parser(Parser::new().max_size(Some(10)));
+        let mut buf = get_buf(b"12:[   ");
+        assert_eq!(
+            Some(Err(Broken::LimitExceeded(Limit::Size))),
+            codec.decode(&mut buf).unwrap()
+        );
+        assert_eq!(None, codec.decode(&mut buf).unwrap());
+        buf.extend_from_slice(b"       ],2:[],");
+        match codec.decode(&mut buf).unwrap() {
+            Some(Err(Broken::Unmatched(_))) => (),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+        assert!(buf.is_empty());
+
+        // A huge length doesn't allocate the whole message in advance
+        let mut buf = get_buf(b"10000000000000000000:[");
+        assert_eq!(None, Netstring::new().decode(&mut buf).unwrap());
+        assert!(buf.capacity() <= 2 * MAX_RESERVE);
+    }
+
+    #[cfg(feature = "cbor")]
+    #[test]
+    fn cbor() {
+        prefixed(Cbor::new(), |value| ::serde_cbor::to_vec(value).unwrap(), 0);
+        prefixed_limits(Cbor::with_parser(Parser::new().max_size(Some(30))));
+    }
+
+    #[cfg(feature = "msgpack")]
+    #[test]
+    fn msgpack() {
+        prefixed(
+            MessagePack::new(),
+            |value| ::rmp_serde::to_vec_named(value).unwrap(),
+            0,
+        );
+        prefixed_limits(MessagePack::with_parser(Parser::new().max_size(Some(30))));
+    }
 }
@@ -15,30 +15,39 @@
 //! [`Client`](struct.Client.html) structure.
 
 use std::error::Error;
-use std::fmt::{Display, Formatter, Result as FmtResult};
-use std::io::{self, Error as IoError, ErrorKind};
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::io::{Error as IoError, ErrorKind};
 use std::collections::HashMap;
-use std::rc::Rc;
-use std::time::Duration;
-use std::cell::RefCell;
+use std::collections::hash_map::Entry;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
-use futures::{Future, IntoFuture, Sink, Stream};
+use bytes::BytesMut;
+use futures::{Async, Future, IntoFuture, Poll, Sink, Stream};
 use futures::future::Either;
 use futures::stream::{self, empty, unfold, Once};
-use futures::unsync::mpsc::{channel, Sender};
-use futures::unsync::oneshot::{channel as one_channel, Sender as OneSender};
+use futures::sync::mpsc::{channel, Sender};
+use futures::sync::oneshot::{channel as one_channel, Sender as OneSender};
 #[cfg(test)]
-use futures::unsync::oneshot::Receiver as OneReceiver;
+use futures::sync::oneshot::Receiver as OneReceiver;
 use serde_json::{to_value, Value};
 use slog::{Discard, Logger};
-use tokio_core::reactor::{Handle, Timeout};
+use tokio_core::reactor::{Handle, Remote, Timeout};
+use tokio_io::{AsyncRead, AsyncWrite};
+// The framing moved to tokio-codec, but the crate and its users build on the one in tokio_io
+#[allow(deprecated)]
+use tokio_io::codec::{Decoder, Encoder, Framed};
 
-use message::{Broken, Message, Notification, Parsed, Request, Response, RpcError};
+use codec::Halves;
+use message::{into_value, Broken, Id, IdGenerator, Message, MessageRef, Notification,
+              NotificationRef, Params, Parsed, ParsedRef, Parser, Request, RequestRef, Response,
+              RpcError, UuidIds, Version};
 use server::{Empty as EmptyServer, Server};
+use stdio::{stdin, stdout, Stdin, Stdout};
 
 /// Thing that terminates the connection once dropped.
 ///
-/// A trick to terminate when all Rcs are forgotten.
+/// A trick to terminate when all Arcs are forgotten.
 struct DropTerminator(Option<OneSender<()>>);
 
 impl Drop for DropTerminator {
@@ -48,23 +57,42 @@ impl Drop for DropTerminator {
     }
 }
 
-type RcDrop = Rc<DropTerminator>;
+type ArcDrop = Arc<DropTerminator>;
 
 /// An internal part of `ServerCtl`.
 ///
-/// The `ServerCtl` is just a thin Rc wrapper around this.
+/// The `ServerCtl` is just a thin Arc wrapper around this.
 struct ServerCtlInternal {
     // Stop processing requests
     stop: bool,
     // Terminate the nice way (if all others also drop)
-    terminator: Option<RcDrop>,
+    terminator: Option<ArcDrop>,
     // Terminate right now
     killer: Option<OneSender<()>>,
     // Info to be able to create a new clients
     idmap: IDMap,
-    handle: Handle,
+    ids: Ids,
+    remote: Remote,
     sender: Option<Sender<Message>>,
     logger: Logger,
+    // The version of the protocol the peer speaks (as far as we know)
+    version: Version,
+    // Who is on the other side, if known
+    peer_credentials: Option<PeerCredentials>,
+}
+
+/// The credentials of the peer process, as reported by the operating system.
+///
+/// They are known for local connections only, eg. the Unix domain sockets (see the `uds` module).
+/// The process ID is not available on all systems.
+#[derive(Copy, Clone, Debug, Eq, Hash, PartialEq)]
+pub struct PeerCredentials {
+    /// The process ID of the peer, if the system provides it.
+    pub pid: Option<u32>,
+    /// The user ID the peer runs as.
+    pub uid: u32,
+    /// The group ID the peer runs as.
+    pub gid: u32,
 }
 
 /// An error indicator when a connection has been already terminated.
@@ -83,20 +111,96 @@ impl Error for AlreadyTerminated {
     }
 }
 
+/// An error indicator when a connection has been closed for inactivity.
+///
+/// If the endpoint has an [`idle_timeout`](struct.Endpoint.html#method.idle_timeout) set and
+/// nothing arrives for that long, the future returned from
+/// [`Endpoint::start`](struct.Endpoint.html#method.start) resolves with an `io::Error` of the
+/// `TimedOut` kind, with this one inside.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct IdleTimeout;
+
+impl Display for IdleTimeout {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Connection idle for too long")
+    }
+}
+
+impl Error for IdleTimeout {
+    fn description(&self) -> &str {
+        "Connection idle for too long"
+    }
+}
+
+/// An error indicator when an RPC is called with the ID of another one still waiting for an answer.
+///
+/// The answers of the two calls couldn't be told apart, so the later call is not sent at all and
+/// its future resolves with an `io::Error` of the `AlreadyExists` kind, with this one inside. This
+/// may happen only with an [ID generator](struct.Endpoint.html#method.id_generator) repeating the
+/// IDs (or producing IDs considered the same by the [matching](enum.IdMatching.html)).
+#[derive(Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct DuplicateId(pub Id);
+
+impl Display for DuplicateId {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "An RPC with the ID {} is already waiting for an answer", self.0)
+    }
+}
+
+impl Error for DuplicateId {
+    fn description(&self) -> &str {
+        "An RPC with the same ID is already waiting for an answer"
+    }
+}
+
+/// A stream wrapper that errors out if the inner stream produces nothing for too long.
+///
+/// Only whole items count, so a partial message sitting in a codec's buffer doesn't keep the
+/// connection alive.
+struct Idle<S> {
+    stream: S,
+    duration: Duration,
+    handle: Handle,
+    // Created on the first poll, so we have a place to report the error
+    timeout: Option<Timeout>,
+}
+
+impl<S: Stream<Error = IoError>> Stream for Idle<S> {
+    type Item = S::Item;
+    type Error = IoError;
+    fn poll(&mut self) -> Poll<Option<S::Item>, IoError> {
+        if self.timeout.is_none() {
+            self.timeout = Some(Timeout::new(self.duration, &self.handle)?);
+        }
+        let timeout = self.timeout.as_mut().unwrap();
+        match self.stream.poll()? {
+            Async::NotReady => (),
+            ready => {
+                timeout.reset(Instant::now() + self.duration);
+                return Ok(ready);
+            },
+        }
+        match timeout.poll()? {
+            Async::Ready(()) => Err(IoError::new(ErrorKind::TimedOut, IdleTimeout)),
+            Async::NotReady => Ok(Async::NotReady),
+        }
+    }
+}
+
 /// A handle to control the server.
 ///
 /// An instance is provided to each [`Server`](../server/trait.Server.html) callback and it can be
 /// used to manipulate the server (currently only to terminate the server) or to create a client
 /// for the use of the server.
 #[derive(Clone)]
-pub struct ServerCtl(Rc<RefCell<ServerCtlInternal>>);
+pub struct ServerCtl(Arc<Mutex<ServerCtlInternal>>);
 
 impl ServerCtl {
     /// Perform a cleanup when terminating in some way.
     ///
     /// And perform some operation on the internal (access for convenience)
     fn cleanup<R, F: FnOnce(&mut ServerCtlInternal) -> R>(&self, f: F) -> R {
-        let mut internal = self.0.borrow_mut();
+        let mut internal = self.0.lock().unwrap();
         debug!(internal.logger, "Server cleanup");
         internal.stop = true;
         internal.sender.take();
@@ -133,18 +237,45 @@ impl ServerCtl {
     /// store it somewhere. If you do clone and keep it, there's a chance for race conditions and
     /// you should check for the error conditions.
     pub fn client(&self) -> Result<Client, AlreadyTerminated> {
-        let internal = self.0.borrow();
+        let internal = self.0.lock().unwrap();
         let terminator = internal.terminator.as_ref().ok_or(AlreadyTerminated)?;
         let sender = internal.sender.as_ref().ok_or(AlreadyTerminated)?;
         Ok(Client::new(
             &internal.idmap,
+            &internal.ids,
             self,
-            &internal.handle,
+            &internal.remote,
             terminator,
             sender,
             internal.logger.clone(),
         ))
     }
+    /// The credentials of the peer, if known.
+    ///
+    /// They are set by [`Endpoint::peer_credentials`](struct.Endpoint.html#method.peer_credentials)
+    /// and allow the server to authorise the peer.
+    pub fn peer_credentials(&self) -> Option<PeerCredentials> {
+        self.0.lock().unwrap().peer_credentials
+    }
+    /// Create a control not attached to any connection.
+    ///
+    /// This is for the servers answering messages one by one, outside of an endpoint. There's no
+    /// client to create from it and terminating it only stops answering through this control.
+    #[cfg(feature = "hyper")]
+    pub(crate) fn detached(remote: Remote, logger: Logger) -> Self {
+        ServerCtl(Arc::new(Mutex::new(ServerCtlInternal {
+            stop: false,
+            terminator: None,
+            killer: None,
+            idmap: IDMap::new(IdMatching::Exact),
+            ids: Ids::new(UuidIds),
+            remote,
+            sender: None,
+            logger,
+            version: Version::V2,
+            peer_credentials: None,
+        })))
+    }
     // This one is for unit tests, not part of the general-purpose API. It creates a dummy
     // ServerCtl that does nothing, but still can be passed to the Server for checking.
     //
@@ -160,29 +291,145 @@ impl ServerCtl {
         let (msg_sender, _msg_receiver) = channel(1);
         let terminator = DropTerminator(Some(drop_sender));
         let core = ::tokio_core::reactor::Core::new().unwrap();
-        let handle = core.handle();
+        let remote = core.remote();
 
-        let ctl = ServerCtl(Rc::new(RefCell::new(ServerCtlInternal {
+        let ctl = ServerCtl(Arc::new(Mutex::new(ServerCtlInternal {
             stop: false,
-            terminator: Some(Rc::new(terminator)),
+            terminator: Some(Arc::new(terminator)),
             killer: Some(kill_sender),
-            idmap: Default::default(),
-            handle: handle,
+            idmap: IDMap::new(IdMatching::Exact),
+            ids: Ids::new(UuidIds),
+            remote,
             sender: Some(msg_sender),
             logger: Logger::root(Discard, o!()),
+            version: Version::V2,
+            peer_credentials: None,
         })));
         (ctl, drop_receiver, kill_receiver)
     }
 }
 
-// Our own BoxFuture & friends that is *not* send. We don't do send.
-type BoxFuture<T, E> = Box<Future<Item = T, Error = E>>;
+// Our own BoxFuture & friends that is *not* send. The server side lives in the reactor thread and
+// doesn't have to be send.
+type BoxFuture<T, E> = Box<dyn Future<Item = T, Error = E>>;
+// The client side may be moved to other threads, so its futures are send.
+type SendBoxFuture<T, E> = Box<dyn Future<Item = T, Error = E> + Send>;
 type FutureMessage = BoxFuture<Option<Message>, IoError>;
-type BoxStream<T, E> = Box<Stream<Item = T, Error = E>>;
+type BoxStream<T, E> = Box<dyn Stream<Item = T, Error = E>>;
 // None in error means end the stream, please
 type FutureMessageStream = BoxStream<FutureMessage, IoError>;
 
-type IDMap = Rc<RefCell<HashMap<String, OneSender<Response>>>>;
+/// How the IDs of received responses are matched to the requests waiting for them.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IdMatching {
+    /// The response must carry exactly the same ID as the request.
+    ///
+    /// This is the default.
+    Exact,
+    /// Strings holding an integer are considered the same ID as the integer (eg. `42` and `"42"`).
+    ///
+    /// Only the integers written the usual way count, so `"+42"` or `"042"` are still different
+    /// IDs.
+    ///
+    /// Some peers don't preserve the type of the ID and turn number-like strings into numbers or
+    /// the other way around. This makes their responses match anyway.
+    Lenient,
+}
+
+impl IdMatching {
+    /// Turns the ID into the form used for matching.
+    fn normalize(self, id: &Id) -> Id {
+        match *id {
+            Id::String(ref s) if self == IdMatching::Lenient => {
+                canonical_number(s).unwrap_or_else(|| id.clone())
+            },
+            _ => id.clone(),
+        }
+    }
+}
+
+/// Turns the string into a numeric ID, if it is the usual way to write the number.
+///
+/// The parsing is more benevolent (eg. accepts leading zeroes), so check the number writes back
+/// the same.
+fn canonical_number(s: &str) -> Option<Id> {
+    let number = s.parse()
+        .map(Id::Number)
+        .or_else(|_| s.parse().map(Id::Unsigned))
+        .ok()?;
+    if number.to_string() == s {
+        Some(number)
+    } else {
+        None
+    }
+}
+
+/// The answer to an RPC, or why it can't come.
+type Answer = Result<Response, IoError>;
+
+/// The RPCs waiting for their responses, keyed by the normalized IDs.
+#[derive(Clone, Debug)]
+struct IDMap {
+    matching: IdMatching,
+    pending: Arc<Mutex<HashMap<Id, OneSender<Answer>>>>,
+}
+
+impl IDMap {
+    fn new(matching: IdMatching) -> Self {
+        IDMap {
+            matching,
+            pending: Default::default(),
+        }
+    }
+    /// Registers the RPC, unless another one with the same ID is already waiting.
+    ///
+    /// The refused RPC gets the `DuplicateId` error right away and false is returned.
+    fn insert(&self, id: &Id, sender: OneSender<Answer>) -> bool {
+        let key = self.matching.normalize(id);
+        match self.pending.lock().unwrap().entry(key) {
+            Entry::Occupied(_) => {
+                let err = IoError::new(ErrorKind::AlreadyExists, DuplicateId(id.clone()));
+                // Nobody may be waiting for the answer anymore, which is fine
+                drop(sender.send(Err(err)));
+                false
+            },
+            Entry::Vacant(entry) => {
+                entry.insert(sender);
+                true
+            },
+        }
+    }
+    fn remove(&self, id: &Id) -> Option<OneSender<Answer>> {
+        let id = self.matching.normalize(id);
+        self.pending.lock().unwrap().remove(&id)
+    }
+    /// Drops all the waiting RPCs, returns how many there were.
+    fn clear(&self) -> usize {
+        let mut pending = self.pending.lock().unwrap();
+        let outstanding = pending.len();
+        pending.clear();
+        outstanding
+    }
+}
+
+/// The ID generator, shared by all the clients of an endpoint.
+#[derive(Clone)]
+pub(crate) struct Ids(Arc<Mutex<Box<dyn IdGenerator>>>);
+
+impl Ids {
+    pub(crate) fn new<Generator: IdGenerator + 'static>(generator: Generator) -> Self {
+        Ids(Arc::new(Mutex::new(Box::new(generator))))
+    }
+    pub(crate) fn next(&self) -> Id {
+        self.0.lock().unwrap().next_id()
+    }
+}
+
+impl Debug for Ids {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Ids")
+    }
+}
 
 // A future::stream::once that takes only the success value, for convenience.
 fn once<T, E>(item: T) -> Once<T, E> {
@@ -239,47 +486,92 @@ fn do_notification<RpcServer: Server>(
     }
 }
 
+fn do_request_ref<RpcServer: Server + 'static>(
+    server: &RpcServer, ctl: &ServerCtl, request: &RequestRef, logger: &Logger
+) -> FutureMessage {
+    // The request borrows from the frame, so take what the answer needs right away
+    let version = request.version();
+    let id = request.id.clone();
+    let answer = move |result: Result<Value, RpcError>| {
+        Some(Message::Response(Response::new(version, id, result)))
+    };
+    match server.rpc_ref(ctl, request) {
+        None => {
+            trace!(logger, "Server refused RPC {}", request.method);
+            let error = RpcError::method_not_found(request.method.clone().into_owned());
+            Box::new(Ok(answer(Err(error))).into_future())
+        },
+        Some(future) => {
+            trace!(logger, "Server accepted RPC {}", request.method);
+            let result = future.into_future().then(move |result| {
+                Ok(answer(result.map(|result| to_value(result).expect("Bad result type"))))
+            });
+            Box::new(result)
+        },
+    }
+}
+
+fn do_notification_ref<RpcServer: Server>(
+    server: &RpcServer, ctl: &ServerCtl, notification: &NotificationRef, logger: &Logger
+) -> FutureMessage {
+    match server.notification_ref(ctl, notification) {
+        None => {
+            trace!(
+                logger,
+                "Server refused notification {}",
+                notification.method
+            );
+            Box::new(Ok(None).into_future())
+        },
+        Some(future) => {
+            trace!(
+                logger,
+                "Server accepted notification {}",
+                notification.method
+            );
+            Box::new(future.into_future().then(|_| Ok(None)))
+        },
+    }
+}
+
 // To process a batch using the same set of parallel executors as the whole server, we produce a
 // stream of the computations which return nothing, but gather the results. Then we add yet another
 // future at the end of that stream that takes the gathered results and wraps them into the real
 // message ‒ the result of the whole batch.
-fn do_batch<RpcServer: Server + 'static>(
-    server: &RpcServer, ctl: &ServerCtl, idmap: &IDMap, logger: &Logger, msg: Vec<Message>
-) -> FutureMessageStream {
+//
+// The caller turns each message of the batch into its stream of futures (right now, so we don't
+// have to keep server long into the future).
+fn do_batch(subs: Vec<FutureMessageStream>) -> FutureMessageStream {
     // Create a large enough channel. We may be unable to pick up the results until the final
     // future gets its turn, so shorter one could lead to a deadlock.
-    let (sender, receiver) = channel(msg.len());
-    // Each message produces a single stream of futures. Create that streams (right now, so we
-    // don't have to keep server long into the future).
-    let small_streams: Vec<_> = msg.into_iter()
+    let (sender, receiver) = channel(subs.len());
+    let small_streams: Vec<_> = subs.into_iter()
         .map(|sub| -> Result<_, IoError> {
             let sender = sender.clone();
-            // This part is a bit convoluted. The do_msg returns a stream of futures. We want to
-            // take each of these futures (the outer and_then), run it to completion (the inner
-            // and_then), send its result through the sender if it provided one and then
-            // convert the result to None.
+            // This part is a bit convoluted. The sub is a stream of futures. We want to take each
+            // of these futures (the outer and_then), run it to completion (the inner and_then),
+            // send its result through the sender if it provided one and then convert the result
+            // to None.
             //
-            // Note that do_msg may return arbitrary number of work futures, but only at most one
-            // of them is supposed to provide a resulting value which would be sent through the
+            // Note that a message may produce arbitrary number of work futures, but only at most
+            // one of them is supposed to provide a resulting value which would be sent through the
             // channel. Unfortunately, there's no way to know which one it'll be, so we have to
             // clone the sender all over the place.
             //
             // Also, it is a bit unfortunate how we need to allocate so many times here. We may try
             // doing something about that in the future, but without implementing custom future and
             // stream types, this seems the best we can do.
-            let all_sent = do_msg(server, ctl, idmap, logger, Ok(sub)).and_then(
-                move |future_message| -> Result<FutureMessage, _> {
-                    let sender = sender.clone();
-                    let msg_sent =
-                        future_message.and_then(move |response: Option<Message>| match response {
-                            None => Either::A(Ok(None).into_future()),
-                            Some(msg) => {
-                                Either::B(sender.send(msg).map_err(shouldnt_happen).map(|_| None))
-                            },
-                        });
-                    Ok(Box::new(msg_sent))
-                },
-            );
+            let all_sent = sub.and_then(move |future_message| -> Result<FutureMessage, _> {
+                let sender = sender.clone();
+                let msg_sent =
+                    future_message.and_then(move |response: Option<Message>| match response {
+                        None => Either::A(Ok(None).into_future()),
+                        Some(msg) => {
+                            Either::B(sender.send(msg).map_err(shouldnt_happen).map(|_| None))
+                        },
+                    });
+                Ok(Box::new(msg_sent))
+            });
             Ok(all_sent)
         })
         .collect();
@@ -300,44 +592,80 @@ fn do_batch<RpcServer: Server + 'static>(
 }
 
 fn do_response(idmap: &IDMap, logger: &Logger, response: Response) -> FutureMessageStream {
-    let maybe_sender = response
-        .id
-        .as_str()
-        .and_then(|id| idmap.borrow_mut().remove(id));
+    let maybe_sender = idmap.remove(&response.id);
     if let Some(sender) = maybe_sender {
-        trace!(logger, "Received an RPC response"; "id" => format!("{:?}", response.id));
+        trace!(logger, "Received an RPC response"; "id" => format!("{}", response.id));
         // Don't care about the result, if the other side went away, it doesn't need the response
         // and that's OK with us.
-        drop(sender.send(response));
+        drop(sender.send(Ok(response)));
     } else {
-        error!(logger, "Unexpected RPC response"; "id" => format!("{:?}", response.id));
+        error!(logger, "Unexpected RPC response"; "id" => format!("{}", response.id));
     }
     Box::new(empty())
 }
 
+// Follow the version the peer speaks, so we talk to it in the same one. Returns if the server is
+// already terminated and the version to answer in.
+fn follow_version(ctl: &ServerCtl, version: Option<Version>) -> (bool, Version) {
+    let mut internal = ctl.0.lock().unwrap();
+    if let Some(version) = version {
+        internal.version = version;
+    }
+    (internal.stop, internal.version)
+}
+
 // Handle single message and turn it into an arbitrary number of futures that may be worked on in
 // parallel, but only at most one of which returns a response message
 fn do_msg<RpcServer: Server + 'static>(
     server: &RpcServer, ctl: &ServerCtl, idmap: &IDMap, logger: &Logger, msg: Parsed
 ) -> FutureMessageStream {
-    let terminated = ctl.0.borrow().stop;
+    let (terminated, version) = follow_version(ctl, msg.as_ref().ok().and_then(Message::version));
     trace!(logger, "Do a message"; "terminated" => terminated, "message" => format!("{:?}", msg));
     if terminated {
-        if let Ok(Message::Response(response)) = msg {
-            do_response(idmap, logger, response);
+        match msg {
+            Ok(Message::Response(response)) => drop(do_response(idmap, logger, response)),
+            // Answers to our batches come as batches too and the client may still be waiting for
+            // them.
+            Ok(Message::Batch(batch)) => for sub in batch {
+                if let Message::Response(response) = sub {
+                    drop(do_response(idmap, logger, response));
+                }
+            },
+            _ => (),
         }
         Box::new(empty())
     } else {
         match msg {
             Err(broken) => {
-                let err: FutureMessage = Box::new(Ok(Some(broken.reply())).into_future());
+                let reply = broken.reply().with_version(version);
+                let err: FutureMessage = Box::new(Ok(Some(reply)).into_future());
                 Box::new(once(err))
             },
             Ok(Message::Request(req)) => Box::new(once(do_request(server, ctl, req, logger))),
             Ok(Message::Notification(notif)) => {
                 Box::new(once(do_notification(server, ctl, &notif, logger)))
             },
-            Ok(Message::Batch(batch)) => do_batch(server, ctl, idmap, logger, batch),
+            Ok(Message::Batch(ref batch)) if batch.is_empty() => {
+                let broken = Broken::Unmatched(Value::Array(Vec::new()));
+                do_msg(server, ctl, idmap, logger, Err(broken))
+            },
+            Ok(Message::Batch(batch)) => {
+                let subs = batch
+                    .into_iter()
+                    .map(|sub| {
+                        // The parser doesn't produce nested batches, but the messages may come
+                        // from elsewhere
+                        let sub = match sub {
+                            nested @ Message::Batch(_) => {
+                                Err(Broken::Unmatched(into_value(nested)))
+                            },
+                            sub => Ok(sub),
+                        };
+                        do_msg(server, ctl, idmap, logger, sub)
+                    })
+                    .collect();
+                do_batch(subs)
+            },
             Ok(Message::UnmatchedSub(value)) => {
                 do_msg(server, ctl, idmap, logger, Err(Broken::Unmatched(value)))
             },
@@ -346,6 +674,67 @@ fn do_msg<RpcServer: Server + 'static>(
     }
 }
 
+// The same as do_msg, but for a message borrowed from a frame. The server gets the requests and
+// notifications with their parameters still raw, the rest has no use for them and goes through
+// do_msg.
+fn do_msg_ref<RpcServer: Server + 'static>(
+    server: &RpcServer, ctl: &ServerCtl, idmap: &IDMap, logger: &Logger, msg: ParsedRef
+) -> FutureMessageStream {
+    let (terminated, _) = follow_version(ctl, msg.as_ref().ok().and_then(MessageRef::version));
+    let msg = match msg {
+        Ok(ref msg) if terminated => Ok(msg.to_message()),
+        Ok(MessageRef::Request(request)) => {
+            trace!(logger, "Do a message"; "message" => format!("{:?}", request));
+            return Box::new(once(do_request_ref(server, ctl, &request, logger)));
+        },
+        Ok(MessageRef::Notification(notification)) => {
+            trace!(logger, "Do a message"; "message" => format!("{:?}", notification));
+            return Box::new(once(do_notification_ref(server, ctl, &notification, logger)));
+        },
+        Ok(MessageRef::Batch(ref batch)) if batch.is_empty() => Ok(Message::Batch(Vec::new())),
+        // The parser doesn't produce nested batches, so this doesn't recurse any deeper
+        Ok(MessageRef::Batch(batch)) => {
+            let subs = batch
+                .into_iter()
+                .map(|sub| do_msg_ref(server, ctl, idmap, logger, Ok(sub)))
+                .collect();
+            return do_batch(subs);
+        },
+        Ok(msg) => Ok(msg.to_message()),
+        Err(broken) => Err(broken),
+    };
+    do_msg(server, ctl, idmap, logger, msg)
+}
+
+fn do_received<RpcServer: Server + 'static>(
+    server: &RpcServer, ctl: &ServerCtl, idmap: &IDMap, parser: &Parser, logger: &Logger,
+    received: Received,
+) -> FutureMessageStream {
+    match received {
+        Received::Parsed(msg) => do_msg(server, ctl, idmap, logger, msg),
+        Received::Frame(frame) => do_msg_ref(server, ctl, idmap, logger, parser.parse_ref(&frame)),
+    }
+}
+
+/// Handle a single message outside of an endpoint.
+///
+/// The message is processed by the same rules as the ones arriving through a connection, but the
+/// answer (if any) is returned instead of being sent. The RPCs of a batch run up to `parallel` at
+/// once.
+#[cfg(feature = "hyper")]
+pub(crate) fn dispatch<RpcServer: Server + 'static>(
+    server: &RpcServer, ctl: &ServerCtl, logger: &Logger, parallel: usize, msg: Parsed
+) -> FutureMessage {
+    let idmap = ctl.0.lock().unwrap().idmap.clone();
+    let answered = do_msg(server, ctl, &idmap, logger, msg)
+        .buffer_unordered(parallel)
+        .filter_map(|message| message)
+        .collect()
+        // At most one of the futures provides an answer
+        .map(|mut answers| answers.pop());
+    Box::new(answered)
+}
+
 /// Internal part of the client.
 ///
 /// Just for convenience, as we need to deconstruct and construct it repeatedly, so this way we
@@ -354,32 +743,142 @@ fn do_msg<RpcServer: Server + 'static>(
 struct ClientData {
     /// Mapping from IDs to the oneshots to wake up the recipient futures.
     idmap: IDMap,
+    /// Where to take IDs for the requests from.
+    ids: Ids,
     /// The control of the server.
     ctl: ServerCtl,
-    handle: Handle,
+    remote: Remote,
     /// Keep the connection alive as long as the client is alive.
-    terminator: RcDrop,
+    terminator: ArcDrop,
     logger: Logger,
 }
 
+impl ClientData {
+    /// Prepare for an answer to the request in `msg`.
+    ///
+    /// This registers the ID of the request, so the answer is routed back once it arrives. It
+    /// returns the future that resolves with the answer (or with None, if the timeout happens
+    /// first). It also returns if the request should be sent ‒ it shouldn't if another request
+    /// with the same ID is still waiting, the future fails with `DuplicateId` then.
+    fn expect_response(&self, msg: &Message, timeout: Option<Duration>) -> (bool, RpcFinished) {
+        let (id, sender, timer, completed) = self.response_channel(msg, timeout);
+        let registered = self.idmap.insert(&id, sender);
+        if registered {
+            if let Some(timer) = timer {
+                self.start_timer(timer);
+            }
+        } else {
+            warn!(self.logger, "Refusing an RPC with a duplicate ID"; "id" => format!("{}", id));
+        }
+        (registered, completed)
+    }
+    /// Create the future for an answer to the request in `msg`, without registering its ID.
+    ///
+    /// The answer is routed back only after the returned sender is inserted into the idmap.
+    /// Dropping the sender instead makes the future fail. Similarly, if there's a timeout, it
+    /// starts only once the returned timer is passed to `start_timer`.
+    fn response_channel(
+        &self, msg: &Message, timeout: Option<Duration>
+    ) -> (Id, OneSender<Answer>, Option<Timer>, RpcFinished) {
+        let id = match *msg {
+            Message::Request(Request { ref id, .. }) => id.clone(),
+            _ => unreachable!("We expect responses only to requests"),
+        };
+        let (sender, receiver) = one_channel();
+        let arc_terminator = self.terminator.clone();
+        let logger_cloned = self.logger.clone();
+        let received = receiver
+            .map_err(|_| IoError::other("Lost connection"))
+            .and_then(|answer| answer)
+            .map(Some)
+            .then(move |r| {
+                trace!(logger_cloned, "Received RPC answer");
+                drop(arc_terminator);
+                r
+            });
+        let (timer, completed): (_, RpcFinished) = match timeout {
+            Some(duration) => {
+                // If we were provided with a timeout, select what happens first. The client may
+                // live in a different thread than the reactor, so the timeout runs inside the
+                // reactor and signals through a channel.
+                let (timeout_sender, timeout_receiver) = one_channel();
+                let timer = Timer {
+                    duration,
+                    sender: timeout_sender,
+                };
+                let idmap = self.idmap.clone();
+                let id = id.clone();
+                let logger_cloned = self.logger.clone();
+                let timed_out = timeout_receiver
+                    .map_err(|_| IoError::other("Timer failed"))
+                    .map(move |_| {
+                        trace!(logger_cloned, "RPC timed out");
+                        // Make sure the ID/sender is removed when the timeout wins. Only a
+                        // started timer fires, so the call is registered under this ID. It may
+                        // be already deleted by a late answer, but that doesn't matter.
+                        idmap.remove(&id);
+                        None
+                    });
+                // The answer goes first, so a refused call reports why even though its timer is
+                // never started
+                let completed = received
+                    .select(timed_out)
+                    .map(|(r, _)| r)
+                    .map_err(|(e, _)| e);
+                (Some(timer), Box::new(completed))
+            },
+            // If we don't have the timeout, simply pass the future to get the response through.
+            None => (None, Box::new(received)),
+        };
+        (id, sender, timer, completed)
+    }
+    /// Start the timeout of a call.
+    ///
+    /// If the timer fails, the sender is dropped without sending.
+    fn start_timer(&self, timer: Timer) {
+        let Timer { duration, sender } = timer;
+        self.remote.spawn(move |handle| {
+            Timeout::new(duration, handle)
+                .into_future()
+                .flatten()
+                .then(move |r| {
+                    if r.is_ok() {
+                        let _ = sender.send(());
+                    }
+                    Ok(())
+                })
+        });
+    }
+}
+
+/// A timeout of a call, not started yet.
+struct Timer {
+    duration: Duration,
+    /// Signals the timeout to the future of the call.
+    sender: OneSender<()>,
+}
+
 /// The client part of the endpoint.
 ///
 /// This can be used to call RPCs and send notifications to the other end. There's no direct
 /// constructor, it is created through the [Endpoint](struct.Endpoint.html).
+///
+/// The client is both `Send` and `Sync`, so it (or its clones) can be moved to other threads and
+/// used from there, while the endpoint itself keeps running inside its reactor.
 #[derive(Clone)]
 pub struct Client {
     sender: Sender<Message>,
     data: ClientData,
 }
 
-pub type Notified = BoxFuture<Client, IoError>;
-pub type RpcFinished = BoxFuture<Option<Response>, IoError>;
-pub type RpcSent = BoxFuture<(Client, RpcFinished), IoError>;
+pub type Notified = SendBoxFuture<Client, IoError>;
+pub type RpcFinished = SendBoxFuture<Option<Response>, IoError>;
+pub type RpcSent = SendBoxFuture<(Client, RpcFinished), IoError>;
 
 impl Client {
     /// A constructor (a private one).
     fn new(
-        idmap: &IDMap, ctl: &ServerCtl, handle: &Handle, terminator: &RcDrop,
+        idmap: &IDMap, ids: &Ids, ctl: &ServerCtl, remote: &Remote, terminator: &ArcDrop,
         sender: &Sender<Message>, logger: Logger,
     ) -> Self {
         debug!(logger, "Creating a new client");
@@ -387,8 +886,9 @@ impl Client {
             sender: sender.clone(),
             data: ClientData {
                 idmap: idmap.clone(),
+                ids: ids.clone(),
                 ctl: ctl.clone(),
-                handle: handle.clone(),
+                remote: remote.clone(),
                 terminator: terminator.clone(),
                 logger,
             },
@@ -400,62 +900,23 @@ impl Client {
     /// once the message is sent. It yields the Client back (it is blocked for the time of sending)
     /// and another future that resolves once the answer is received (or once a timeout happens, in
     /// which case the result is None).
-    pub fn call(self, method: String, params: Option<Value>, timeout: Option<Duration>) -> RpcSent {
+    pub fn call(
+        self, method: String, params: Option<Params>, timeout: Option<Duration>
+    ) -> RpcSent {
         // We have to deconstruct self now, because the sender's send takes ownership for it for a
         // while. We construct it back once the message is passed on.
         let data = self.data;
         trace!(data.logger, "Calling RPC {}", method);
-        let msg = Message::request(method, params);
-        let id = match msg {
-            Message::Request(Request {
-                id: Value::String(ref id),
-                ..
-            }) => id.clone(),
-            _ => unreachable!("We produce only string IDs"),
-        };
-        let (sender, receiver) = one_channel();
-        let rc_terminator = data.terminator.clone();
-        let logger_cloned = data.logger.clone();
-        let received = receiver
-            .map_err(|_| IoError::new(io::ErrorKind::Other, "Lost connection"))
-            .map(Some)
-            .then(move |r| {
-                trace!(logger_cloned, "Received RPC answer");
-                drop(rc_terminator);
-                r
-            });
-        let completed: RpcFinished = match timeout {
-            Some(time) => {
-                // If we were provided with a timeout, select what happens first.
-                let timeout = match Timeout::new(time, &data.handle) {
-                    Err(e) => return Box::new(Err(e).into_future()),
-                    Ok(t) => t,
-                };
-                let idmap = data.idmap.clone();
-                let id = id.clone();
-                let logger_cloned = data.logger.clone();
-                let completed = timeout
-                    .then(move |r| {
-                        trace!(logger_cloned, "RPC timed out");
-                        r
-                    })
-                    .map(|_| None)
-                    .select(received)
-                    .map(|(r, _)| r)
-                    .map_err(|(e, _)| e)
-                    // Make sure the ID/sender is removed even when timeout wins.
-                    // This is a NOOP in case the real result arrives, since it is already deleted
-                    // by then, but that doesn't matter and this is simpler.
-                    .then(move |r| {
-                        idmap.borrow_mut().remove(&id);
-                        r
-                    });
-                Box::new(completed)
-            },
-            // If we don't have the timeout, simply pass the future to get the response through.
-            None => Box::new(received),
-        };
-        data.idmap.borrow_mut().insert(id, sender);
+        let msg = Message::request_with_id(method, params, data.ids.next());
+        let (registered, completed) = data.expect_response(&msg, timeout);
+        if !registered {
+            // The answer couldn't be told apart from the one to the other call, don't even send it
+            let client = Client {
+                sender: self.sender,
+                data,
+            };
+            return Box::new(Ok((client, completed)).into_future());
+        }
         // Ensure the connection is kept alive until the answer comes
         let sent = self.sender
             .send(msg)
@@ -470,7 +931,7 @@ impl Client {
     ///
     /// It creates a notification message and sends it. It returs a future that resolves once the
     /// message is sent and yields the client back for further use.
-    pub fn notify(self, method: String, params: Option<Value>) -> Notified {
+    pub fn notify(self, method: String, params: Option<Params>) -> Notified {
         let data = self.data;
         trace!(data.logger, "Sending notification {}", method);
         let future = self.sender
@@ -479,6 +940,19 @@ impl Client {
             .map(move |sender| Client { sender, data });
         Box::new(future)
     }
+    /// Start a batch.
+    ///
+    /// The returned [`Batch`](struct.Batch.html) collects RPC calls and notifications and sends
+    /// them to the other side as a single message, saving round trips. The client is handed back
+    /// once the batch is sent.
+    pub fn batch(self) -> Batch {
+        trace!(self.data.logger, "Starting a batch");
+        Batch {
+            client: self,
+            messages: Vec::new(),
+            pending: Vec::new(),
+        }
+    }
     /// Get the server control.
     ///
     /// That allows terminating the server, etc.
@@ -487,6 +961,126 @@ impl Client {
     }
 }
 
+/// A batch of RPC calls and notifications.
+///
+/// It is created by the [`Client::batch`](struct.Client.html#method.batch) method. The calls and
+/// notifications are only collected here and all of them are sent at once, as a single
+/// `Message::Batch`, when [`send`](#method.send) is called.
+///
+/// If the batch is dropped without being sent, the futures of its calls fail.
+pub struct Batch {
+    client: Client,
+    messages: Vec<Message>,
+    /// The calls waiting to be registered in the idmap and their timeouts waiting to be started,
+    /// both once the batch is sent.
+    pending: Vec<(Id, OneSender<Answer>, Option<Timer>)>,
+}
+
+impl Batch {
+    /// Add an RPC call to the batch.
+    ///
+    /// It returns a future that resolves once the answer to this call is received (or once a
+    /// timeout happens, in which case the result is None). The timeout starts only once the batch
+    /// is sent, so a call can't time out before it is even on its way to the other side.
+    pub fn call(
+        &mut self, method: String, params: Option<Params>, timeout: Option<Duration>
+    ) -> RpcFinished {
+        trace!(self.client.data.logger, "Adding RPC {} to a batch", method);
+        let msg = Message::request_with_id(method, params, self.client.data.ids.next());
+        let (id, sender, timer, completed) = self.client.data.response_channel(&msg, timeout);
+        self.pending.push((id, sender, timer));
+        self.messages.push(msg);
+        completed
+    }
+    /// Add a notification to the batch.
+    pub fn notify(&mut self, method: String, params: Option<Params>) {
+        trace!(self.client.data.logger, "Adding notification {} to a batch", method);
+        self.messages.push(Message::notification(method, params));
+    }
+    /// Send the batch.
+    ///
+    /// It returns a future that resolves once the batch is sent and yields the client back for
+    /// further use. As the specification doesn't allow empty batches, nothing is sent if no calls
+    /// or notifications were added.
+    pub fn send(self) -> Notified {
+        let Batch {
+            client,
+            messages,
+            pending,
+        } = self;
+        let data = client.data;
+        let mut timers = Vec::new();
+        let mut pending = pending.into_iter();
+        let mut sent = Vec::with_capacity(messages.len());
+        // The calls are in the same order as their requests
+        for msg in messages {
+            if let Message::Request(_) = msg {
+                let (id, sender, timer) = pending.next().expect("A call without its request");
+                if !data.idmap.insert(&id, sender) {
+                    // Like with a single call, don't send it, its future already fails
+                    warn!(data.logger, "Refusing an RPC with a duplicate ID";
+                          "id" => format!("{}", id));
+                    continue;
+                }
+                timers.extend(timer);
+            }
+            sent.push(msg);
+        }
+        let messages = sent;
+        if messages.is_empty() {
+            trace!(data.logger, "Not sending an empty batch");
+            let client = Client {
+                sender: client.sender,
+                data,
+            };
+            return Box::new(Ok(client).into_future());
+        }
+        trace!(data.logger, "Sending a batch"; "size" => messages.len());
+        let future = client
+            .sender
+            .send(Message::Batch(messages))
+            .map_err(shouldnt_happen)
+            .map(move |sender| {
+                for timer in timers {
+                    data.start_timer(timer);
+                }
+                Client { sender, data }
+            });
+        Box::new(future)
+    }
+}
+
+/// What the endpoint receives from the connection.
+///
+/// The connection either parses the messages itself (eg. framed by the
+/// [`LineCodec`](../codec/struct.Line.html)) or provides just the frames (eg. framed by the
+/// [`RawLine`](../codec/struct.RawLine.html) codec). The endpoint parses the frames by its
+/// [parser](struct.Endpoint.html#method.parser) into the borrowed
+/// [`MessageRef`](../message/enum.MessageRef.html) and the server gets the requests and
+/// notifications with the parameters still raw (see
+/// [`Server::rpc_ref`](../server/trait.Server.html#method.rpc_ref)).
+///
+/// The connection may yield anything convertible into this.
+#[derive(Clone, Debug)]
+pub enum Received {
+    /// A message parsed by the connection.
+    Parsed(Parsed),
+    /// A frame holding a single message, to be parsed by the endpoint.
+    Frame(BytesMut),
+}
+
+impl From<Parsed> for Received {
+    fn from(parsed: Parsed) -> Self {
+        Received::Parsed(parsed)
+    }
+}
+
+impl From<BytesMut> for Received {
+    fn from(frame: BytesMut) -> Self {
+        Received::Frame(frame)
+    }
+}
+
 /// The builder structure for the end point.
 ///
 /// This is used to create the endpoint ‒ both the server and client part at once.
@@ -509,7 +1103,7 @@ impl Client {
 /// # use tokio_core::net::TcpStream;
 /// # use tokio_io::AsyncRead;
 /// # use tokio_jsonrpc::{LineCodec, Server, ServerCtl, RpcError, Endpoint};
-/// # use tokio_jsonrpc::message::Response;
+/// # use tokio_jsonrpc::message::{Params, Response};
 /// # use futures::{Future, Stream};
 /// # use serde_json::Value;
 /// #
@@ -524,7 +1118,7 @@ impl Client {
 ///             .start(&handle);
 ///         // Call a method with some parameters and a 10 seconds timeout
 ///         client.call("request".to_owned(),
-///                     Some(json!(["param1", "param2"])),
+///                     Some(Params::Positional(vec![json!("param1"), json!("param2")])),
 ///                     Some(Duration::new(10, 0)))
 ///             .and_then(|(_client, future_result)| future_result)
 ///             .map(|response| {
@@ -544,11 +1138,18 @@ pub struct Endpoint<Connection, RpcServer> {
     server: RpcServer,
     parallel: usize,
     logger: Logger,
+    idle_timeout: Option<Duration>,
+    ids: Ids,
+    id_matching: IdMatching,
+    version: Version,
+    peer_credentials: Option<PeerCredentials>,
+    parser: Parser,
 }
 
 impl<Connection, RpcServer> Endpoint<Connection, RpcServer>
 where
-    Connection: Stream<Item = Parsed, Error = IoError>,
+    Connection: Stream<Error = IoError>,
+    Connection::Item: Into<Received>,
     Connection: Sink<SinkItem = Message, SinkError = IoError>,
     Connection: Send + 'static,
     RpcServer: Server + 'static,
@@ -562,6 +1163,12 @@ where
             server,
             parallel: 1,
             logger: Logger::root(Discard, o!()),
+            idle_timeout: None,
+            ids: Ids::new(UuidIds),
+            id_matching: IdMatching::Exact,
+            version: Version::V2,
+            peer_credentials: None,
+            parser: Parser::new(),
         }
     }
     /// Set how many RPCs may be process in parallel.
@@ -584,6 +1191,77 @@ where
     pub fn logger(self, logger: Logger) -> Self {
         Endpoint { logger, ..self }
     }
+    /// Sets the generator of IDs for the RPCs called by the clients of this endpoint.
+    ///
+    /// By default, the IDs are random UUIDs ([`UuidIds`](../message/struct.UuidIds.html)). Other
+    /// generators may produce shorter IDs (eg.
+    /// [`SequentialIds`](../message/struct.SequentialIds.html)) or deterministic ones for testing.
+    pub fn id_generator<Generator: IdGenerator + 'static>(self, generator: Generator) -> Self {
+        Endpoint {
+            ids: Ids::new(generator),
+            ..self
+        }
+    }
+    /// Sets how the IDs of responses are matched to the outstanding RPCs.
+    ///
+    /// By default, the matching is [`Exact`](enum.IdMatching.html#variant.Exact). Use
+    /// [`Lenient`](enum.IdMatching.html#variant.Lenient) when talking to a peer that changes
+    /// number-like string IDs into numbers (or the other way around). Note that the default UUID
+    /// IDs never look like numbers, so this is useful mostly with a different
+    /// [ID generator](#method.id_generator).
+    pub fn id_matching(self, id_matching: IdMatching) -> Self {
+        Endpoint {
+            id_matching,
+            ..self
+        }
+    }
+    /// Sets the version of the protocol to talk to the peer.
+    ///
+    /// The endpoint follows the version of the messages it receives from the peer. Both the
+    /// answers and the calls from the clients are sent in the version the peer last used. This sets
+    /// the version to use before the peer sends anything, which is useful for clients of JSON-RPC
+    /// 1.0 servers. The default is [`V2`](../message/enum.Version.html#variant.V2).
+    ///
+    /// Note that to receive the 1.0 messages, the codec needs to be configured to accept them
+    /// (see [`Parser::accept_v1`](../message/struct.Parser.html#method.accept_v1)).
+    pub fn version(self, version: Version) -> Self {
+        Endpoint { version, ..self }
+    }
+    /// Sets the inactivity timeout.
+    ///
+    /// If no message is received from the other side for this long, the connection is closed.
+    /// Incomplete messages don't count, so a peer sending a message very slowly (or never finishing
+    /// it) times out as well. In such case the future returned from `start` resolves with an
+    /// `io::Error` of the `TimedOut` kind, wrapping [`IdleTimeout`](struct.IdleTimeout.html).
+    ///
+    /// By default, there's no timeout.
+    pub fn idle_timeout(self, timeout: Duration) -> Self {
+        Endpoint {
+            idle_timeout: Some(timeout),
+            ..self
+        }
+    }
+    /// Sets the credentials of the peer.
+    ///
+    /// They are provided to the server through
+    /// [`ServerCtl::peer_credentials`](struct.ServerCtl.html#method.peer_credentials). The
+    /// connections with known peers set them automatically (eg. the ones from the
+    /// `uds` module).
+    pub fn peer_credentials(self, credentials: PeerCredentials) -> Self {
+        Endpoint {
+            peer_credentials: Some(credentials),
+            ..self
+        }
+    }
+    /// Sets the parser of the frames.
+    ///
+    /// It is used only if the connection provides [raw frames](enum.Received.html) instead of
+    /// parsed messages. Such connections should limit the size of the frames by the same parser
+    /// (eg. [`RawLine::with_parser`](../codec/struct.RawLine.html#method.with_parser)). The
+    /// default is [`Parser::new`](../message/struct.Parser.html#method.new).
+    pub fn parser(self, parser: Parser) -> Self {
+        Endpoint { parser, ..self }
+    }
     /// Start the endpoint.
     ///
     /// Once all configuration is set, this creates the actual endpoint pair ‒ both the server and
@@ -596,31 +1274,46 @@ where
     // TODO: Some cleanup. This looks a *bit* hairy and complex.
     // TODO: Should we return a better error/return the error once thing resolves?
     pub fn start(self, handle: &Handle) -> (Client, Box<Future<Item = (), Error = IoError>>) {
-        debug!(self.logger, "Starting endpoint"; "parallel" => self.parallel);
+        debug!(self.logger, "Starting endpoint"; "parallel" => self.parallel,
+               "idle_timeout" => format!("{:?}", self.idle_timeout));
         let logger = self.logger;
         let (terminator_sender, terminator_receiver) = one_channel();
         let (killer_sender, killer_receiver) = one_channel();
         let (sender, receiver) = channel(32);
-        let idmap = Rc::new(RefCell::new(HashMap::new()));
-        let rc_terminator = Rc::new(DropTerminator(Some(terminator_sender)));
-        let ctl = ServerCtl(Rc::new(RefCell::new(ServerCtlInternal {
+        let idmap = IDMap::new(self.id_matching);
+        let arc_terminator = Arc::new(DropTerminator(Some(terminator_sender)));
+        let ctl = ServerCtl(Arc::new(Mutex::new(ServerCtlInternal {
             stop: false,
-            terminator: Some(rc_terminator.clone()),
+            terminator: Some(arc_terminator.clone()),
             killer: Some(killer_sender),
             idmap: idmap.clone(),
-            handle: handle.clone(),
+            ids: self.ids.clone(),
+            remote: handle.remote().clone(),
             sender: Some(sender.clone()),
             logger: logger.clone(),
+            version: self.version,
+            peer_credentials: self.peer_credentials,
         })));
         let client = Client::new(
             &idmap,
+            &self.ids,
             &ctl,
-            handle,
-            &rc_terminator,
+            handle.remote(),
+            &arc_terminator,
             &sender,
             logger.clone(),
         );
         let (sink, stream) = self.connection.split();
+        let stream = stream.map(Into::into);
+        let stream: BoxStream<Received, IoError> = match self.idle_timeout {
+            Some(duration) => Box::new(Idle {
+                stream,
+                duration,
+                handle: handle.clone(),
+                timeout: None,
+            }),
+            None => Box::new(stream),
+        };
         // Create a future for each received item that'll return something. Run some of them in
         // parallel.
 
@@ -634,6 +1327,7 @@ where
             .into_stream();
         // Move out of self, otherwise the closure captures self, not only server :-|
         let server = self.server;
+        let parser = self.parser;
         server.initialized(&ctl);
         let idmap_cloned = idmap.clone();
         let logger_cloned = logger.clone();
@@ -641,14 +1335,18 @@ where
         // futures)
         let ctl_clone = ctl.clone();
         let cleaner = unfold((), move |_| -> Option<Result<_, _>> {
-            let mut idmap = idmap_cloned.borrow_mut();
-            debug!(logger_cloned, "Dropping unanswered RPCs (EOS)"; "outstanding" => idmap.len());
-            idmap.clear();
+            let outstanding = idmap_cloned.clear();
+            debug!(logger_cloned, "Dropping unanswered RPCs (EOS)"; "outstanding" => outstanding);
             // Terminate the server manually when we reach the end of input, because it holds the
             // client alive ‒ this will end the messages from the client endpoint.
             ctl_clone.terminate();
             Some(Ok((None, ())))
         });
+        // The client messages are sent in the version the peer speaks
+        let ctl_clone = ctl.clone();
+        let requests = receiver
+            .map(move |msg| msg.with_version(ctl_clone.0.lock().unwrap().version))
+            .map_err(shouldnt_happen);
         let idmap_cloned = idmap.clone();
         let logger_cloned = logger.clone();
         let answers = stream
@@ -656,13 +1354,15 @@ where
             .chain(cleaner)
             .select(terminator)
             .take_while(|m| Ok(m.is_some()))
-            .map(move |parsed| do_msg(&server, &ctl, &idmap, &logger_cloned, parsed.unwrap()))
+            .map(move |received| {
+                do_received(&server, &ctl, &idmap, &parser, &logger_cloned, received.unwrap())
+            })
             .flatten()
             .buffer_unordered(self.parallel)
             .filter_map(|message| message);
         let logger_cloned = logger.clone();
         // Take both the client RPCs and the answers
-        let outbound = answers.select(receiver.map_err(shouldnt_happen));
+        let outbound = answers.select(requests);
         let (error_sender, error_receiver) = one_channel::<Option<IoError>>();
         // And send them all (or kill it, if it happens first)
         let transmitted = sink.send_all(outbound)
@@ -671,9 +1371,8 @@ where
             .then(move |result| {
                 // This will hopefully kill the RPC futures
                 // We kill on both ends, because we may kill the connection or the other side may.
-                let mut idmap = idmap_cloned.borrow_mut();
-                debug!(logger_cloned, "Dropping unanswered RPCs"; "outstanding" => idmap.len());
-                idmap.clear();
+                let outstanding = idmap_cloned.clear();
+                debug!(logger_cloned, "Dropping unanswered RPCs"; "outstanding" => outstanding);
                 match result {
                     Ok(_) => {
                         debug!(logger_cloned, "Outbound stream ended successfully");
@@ -705,9 +1404,65 @@ where
     }
 }
 
+#[allow(deprecated)]
+impl<Io, Codec, RpcServer> Endpoint<Framed<Io, Codec>, RpcServer>
+where
+    Io: AsyncRead + AsyncWrite + Send + 'static,
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Send + 'static,
+    RpcServer: Server + 'static,
+{
+    /// Create the endpoint builder directly on top of an IO object.
+    ///
+    /// The connection is framed with the provided codec (eg.
+    /// [`LineCodec`](../codec/struct.Line.html) or
+    /// [`RawLine`](../codec/struct.RawLine.html)), so it doesn't have to be done manually.
+    pub fn from_io(io: Io, codec: Codec, server: RpcServer) -> Self {
+        Self::new(io.framed(codec), server)
+    }
+}
+
+impl<Reader, Writer, Codec, RpcServer> Endpoint<Halves<Reader, Writer, Codec>, RpcServer>
+where
+    Reader: AsyncRead + Send + 'static,
+    Writer: AsyncWrite + Send + 'static,
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Clone + Send + 'static,
+    RpcServer: Server + 'static,
+{
+    /// Create the endpoint builder on top of separate reading and writing halves.
+    ///
+    /// This is for connections where the two directions are different objects, like a pair of
+    /// pipes or stdin and stdout of a child process. Each half is framed with its own copy of the
+    /// provided codec, which may produce the raw frames as well (eg.
+    /// [`RawLine`](../codec/struct.RawLine.html)).
+    pub fn from_halves(reader: Reader, writer: Writer, codec: Codec, server: RpcServer) -> Self {
+        Self::new(Halves::new(reader, writer, codec), server)
+    }
+}
+
+impl<Codec, RpcServer> Endpoint<Halves<Stdin, Stdout, Codec>, RpcServer>
+where
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Clone + Send + 'static,
+    RpcServer: Server + 'static,
+{
+    /// Create the endpoint builder on the standard input and output of the process.
+    ///
+    /// The end of the input terminates the endpoint the same way as closing a connection does.
+    /// See the [`stdio`](../stdio/index.html) module for details.
+    pub fn stdio(codec: Codec, server: RpcServer) -> Self {
+        Self::from_halves(stdin(), stdout(), codec, server)
+    }
+}
+
 impl<Connection> Endpoint<Connection, EmptyServer>
 where
-    Connection: Stream<Item = Parsed, Error = IoError>,
+    Connection: Stream<Error = IoError>,
+    Connection::Item: Into<Received>,
     Connection: Sink<SinkItem = Message, SinkError = IoError>,
     Connection: Send + 'static,
 {
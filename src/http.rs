@@ -0,0 +1,351 @@
+// Copyright 2017 tokio-jsonrpc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The HTTP transport.
+//!
+//! Available with the `hyper` feature. Unlike the [`Endpoint`](../endpoint/struct.Endpoint.html),
+//! there's no long-lived connection in both directions. Each message is sent in the body of a
+//! `POST` request and the answer comes back in the body of its response.
+//!
+//! The [`HttpServer`](struct.HttpServer.html) is a [hyper](https://docs.rs/hyper) service that
+//! hands the messages to any [`Server`](../server/trait.Server.html). The messages are handled
+//! the same way as by the endpoint, including batches. Requests with no answer (eg. notifications)
+//! are replied with `204 No Content`.
+//!
+//! The [`HttpClient`](struct.HttpClient.html) posts the calls and notifications to a URL.
+//!
+//! ```rust,no_run
+//! # extern crate tokio_core;
+//! # extern crate tokio_jsonrpc;
+//! # extern crate futures;
+//! # use futures::{Future, Stream};
+//! # use tokio_core::reactor::Core;
+//! # use tokio_core::net::TcpListener;
+//! # use tokio_jsonrpc::http::HttpServer;
+//! # use tokio_jsonrpc::server::Empty;
+//! # fn main() {
+//! let mut core = Core::new().unwrap();
+//! let handle = core.handle();
+//! let listener = TcpListener::bind(&"127.0.0.1:8080".parse().unwrap(), &handle).unwrap();
+//! // Use a real server here
+//! let server = HttpServer::new(Empty, &handle);
+//! let connections = listener.incoming().for_each(|(stream, _)| {
+//!     handle.spawn(server.serve(stream).map_err(|_| ()));
+//!     Ok(())
+//! });
+//! core.run(connections).unwrap();
+//! # }
+//! ```
+
+use std::io::{Error as IoError, ErrorKind};
+use std::rc::Rc;
+use std::time::{Duration, Instant};
+
+use futures::{Future, IntoFuture, Stream};
+use hyper::{Body, Client as HyperClient, Method, Request, Response as HttpResponse, StatusCode,
+            Uri};
+use hyper::client::HttpConnector;
+use hyper::header::{HeaderValue, ALLOW, CONTENT_TYPE};
+use hyper::server::conn::Http;
+use hyper::service::Service;
+use serde_json::to_vec;
+use slog::{Discard, Logger};
+use tokio::timer::Delay;
+use tokio_core::reactor::Handle;
+use tokio_io::{AsyncRead, AsyncWrite};
+
+use endpoint::{dispatch, Ids, RpcFinished, ServerCtl};
+use message::{Broken, Id, IdGenerator, Limit, Message, Params, Parsed, Parser, UuidIds, Version};
+use server::Server;
+
+/// A reply with no body.
+fn empty_reply(status: StatusCode) -> HttpResponse<Body> {
+    let mut reply = HttpResponse::new(Body::empty());
+    *reply.status_mut() = status;
+    reply
+}
+
+/// A service answering the JSON RPC messages posted over HTTP.
+///
+/// It hands the messages to the wrapped [`Server`](../server/trait.Server.html), following the
+/// same rules as the [`Endpoint`](../endpoint/struct.Endpoint.html). The answer is sent in the
+/// body of the reply, with `204 No Content` if there's none. Methods other than `POST` are
+/// refused with `405 Method Not Allowed`.
+///
+/// Each HTTP request gets its own [`ServerCtl`](../endpoint/struct.ServerCtl.html). As there's
+/// no way to call back over HTTP, it provides no client and terminating it only concerns the
+/// single request.
+///
+/// As the server's futures don't need to be `Send`, the service lives in the reactor thread.
+pub struct HttpServer<RpcServer> {
+    server: Rc<RpcServer>,
+    handle: Handle,
+    parser: Parser,
+    parallel: usize,
+    logger: Logger,
+}
+
+// Derive would require RpcServer: Clone, which is not needed.
+impl<RpcServer> Clone for HttpServer<RpcServer> {
+    fn clone(&self) -> Self {
+        HttpServer {
+            server: Rc::clone(&self.server),
+            handle: self.handle.clone(),
+            parser: self.parser.clone(),
+            parallel: self.parallel,
+            logger: self.logger.clone(),
+        }
+    }
+}
+
+impl<RpcServer: Server + 'static> HttpServer<RpcServer> {
+    /// Creates the service around a server.
+    ///
+    /// The handle is used to run the connections served by [`serve`](#method.serve).
+    pub fn new(server: RpcServer, handle: &Handle) -> Self {
+        HttpServer {
+            server: Rc::new(server),
+            handle: handle.clone(),
+            parser: Parser::new(),
+            parallel: 1,
+            logger: Logger::root(Discard, o!()),
+        }
+    }
+    /// Sets the parser of the posted messages.
+    ///
+    /// The size limit of the parser (if any) applies to the body of the HTTP request. If the
+    /// [limits are fatal](../message/struct.Parser.html#method.fatal_limits), a too large body is
+    /// refused with `413 Payload Too Large`, otherwise it is answered with an invalid request
+    /// error.
+    pub fn parser(self, parser: Parser) -> Self {
+        HttpServer { parser, ..self }
+    }
+    /// Sets how many RPCs of a single batch may be processed in parallel.
+    ///
+    /// See [`Endpoint::parallel`](../endpoint/struct.Endpoint.html#method.parallel).
+    pub fn parallel(self, parallel: usize) -> Self {
+        HttpServer { parallel, ..self }
+    }
+    /// Sets the logger used by the server.
+    pub fn logger(self, logger: Logger) -> Self {
+        HttpServer { logger, ..self }
+    }
+    /// Serves a single connection (eg. an accepted TCP stream).
+    ///
+    /// The returned future resolves once the connection is closed.
+    pub fn serve<Io>(&self, io: Io) -> Box<dyn Future<Item = (), Error = IoError>>
+    where
+        Io: AsyncRead + AsyncWrite + 'static,
+    {
+        let served = Http::new()
+            .with_executor(self.handle.clone())
+            .serve_connection(io, self.clone())
+            .map_err(IoError::other);
+        Box::new(served)
+    }
+}
+
+impl<RpcServer: Server + 'static> Service for HttpServer<RpcServer> {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = IoError;
+    type Future = Box<dyn Future<Item = HttpResponse<Body>, Error = IoError>>;
+    fn call(&mut self, request: Request<Body>) -> Self::Future {
+        if request.method() != Method::POST {
+            debug!(self.logger, "Refusing HTTP method"; "method" => request.method().as_str());
+            let mut reply = empty_reply(StatusCode::METHOD_NOT_ALLOWED);
+            reply
+                .headers_mut()
+                .insert(ALLOW, HeaderValue::from_static("POST"));
+            return Box::new(Ok(reply).into_future());
+        }
+        let parser = self.parser.clone();
+        // Don't keep more than the limit, but read the rest of the body anyway
+        let body = request.into_body().map_err(IoError::other).fold(
+            Some(Vec::new()),
+            move |body, chunk| -> Result<_, IoError> {
+                Ok(body.and_then(|mut body| {
+                    body.extend_from_slice(&chunk);
+                    if parser.size_exceeded(body.len()) {
+                        None
+                    } else {
+                        Some(body)
+                    }
+                }))
+            },
+        );
+        let server = Rc::clone(&self.server);
+        let ctl = ServerCtl::detached(self.handle.remote().clone(), self.logger.clone());
+        let parser = self.parser.clone();
+        let parallel = self.parallel;
+        let logger = self.logger.clone();
+        let answered = body.and_then(move |body| {
+            let parsed = match body {
+                Some(body) => parser.parse_slice(&body),
+                None if parser.limits_fatal() => {
+                    debug!(logger, "Refusing too large HTTP request");
+                    let reply = empty_reply(StatusCode::PAYLOAD_TOO_LARGE);
+                    return Box::new(Ok(reply).into_future()) as Self::Future;
+                },
+                None => Err(Broken::LimitExceeded(Limit::Size)),
+            };
+            let answered = dispatch(&*server, &ctl, &logger, parallel, parsed).and_then(|answer| {
+                let reply = match answer {
+                    None => empty_reply(StatusCode::NO_CONTENT),
+                    Some(message) => {
+                        let mut reply = HttpResponse::new(Body::from(to_vec(&message)?));
+                        reply
+                            .headers_mut()
+                            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                        reply
+                    },
+                };
+                Ok(reply)
+            });
+            Box::new(answered)
+        });
+        Box::new(answered)
+    }
+}
+
+/// The future of the answer to a message posted by the [`HttpClient`](struct.HttpClient.html).
+///
+/// It resolves with `None` if the server sent no answer.
+pub type Posted = Box<dyn Future<Item = Option<Parsed>, Error = IoError> + Send>;
+
+/// A client calling RPCs over HTTP.
+///
+/// Each call or notification is sent as a separate `POST` request to the URL. It is similar to the
+/// [`Client`](../endpoint/struct.Client.html) of the endpoint, but there's no connection to keep,
+/// the calls are independent of each other.
+///
+/// The client is both `Send` and `Sync`, but it needs to run inside a tokio runtime (eg. the
+/// reactor core).
+#[derive(Clone, Debug)]
+pub struct HttpClient {
+    client: HyperClient<HttpConnector>,
+    uri: Uri,
+    ids: Ids,
+    parser: Parser,
+    version: Version,
+}
+
+impl HttpClient {
+    /// Creates a client posting to the given `http://` URL.
+    pub fn new(uri: Uri) -> Self {
+        HttpClient {
+            client: HyperClient::new(),
+            uri,
+            ids: Ids::new(UuidIds),
+            parser: Parser::new(),
+            version: Version::V2,
+        }
+    }
+    /// Sets the generator of the IDs of the calls.
+    ///
+    /// See [`Endpoint::id_generator`](../endpoint/struct.Endpoint.html#method.id_generator).
+    pub fn id_generator<Generator: IdGenerator + 'static>(self, generator: Generator) -> Self {
+        HttpClient {
+            ids: Ids::new(generator),
+            ..self
+        }
+    }
+    /// Sets the parser of the answers.
+    pub fn parser(self, parser: Parser) -> Self {
+        HttpClient { parser, ..self }
+    }
+    /// Sets the version of the protocol of the sent messages.
+    ///
+    /// The default is [`V2`](../message/enum.Version.html#variant.V2).
+    pub fn version(self, version: Version) -> Self {
+        HttpClient { version, ..self }
+    }
+    /// Posts a message and returns the answer to it.
+    ///
+    /// This is the lower-level interface, allowing to send any message (eg. a batch). The answer is
+    /// parsed, but not checked in any way.
+    pub fn send(&self, msg: Message) -> Posted {
+        let body = match to_vec(&msg.with_version(self.version)) {
+            Ok(body) => body,
+            Err(e) => return Box::new(Err(IoError::from(e)).into_future()),
+        };
+        let mut request = Request::new(Body::from(body));
+        *request.method_mut() = Method::POST;
+        *request.uri_mut() = self.uri.clone();
+        request
+            .headers_mut()
+            .insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let parser = self.parser.clone();
+        let answered = self.client
+            .request(request)
+            .map_err(IoError::other)
+            .and_then(|reply| {
+                let status = reply.status();
+                if !status.is_success() {
+                    return Err(IoError::other(format!("HTTP error {}", status)));
+                }
+                Ok(reply.into_body().concat2().map_err(IoError::other))
+            })
+            .flatten()
+            .map(move |body| {
+                if body.iter().all(u8::is_ascii_whitespace) {
+                    None
+                } else {
+                    Some(parser.parse_slice(&body))
+                }
+            });
+        Box::new(answered)
+    }
+    /// Calls an RPC.
+    ///
+    /// The returned future resolves with the response, or with `None` if the timeout happens
+    /// first. An error response with a null ID (eg. when the server found the request invalid) is
+    /// returned too, as the server couldn't tie it to the call. Any other answer that isn't the
+    /// response to the call is an I/O error.
+    pub fn call(
+        &self, method: String, params: Option<Params>, timeout: Option<Duration>
+    ) -> RpcFinished {
+        let id = self.ids.next();
+        let msg = Message::request_with_id(method, params, id.clone());
+        let answered = self.send(msg).and_then(move |answer| match answer {
+            Some(Ok(Message::Response(ref response))) if response.id == id => {
+                Ok(Some(response.clone()))
+            },
+            Some(Ok(Message::Response(ref response)))
+                if response.id == Id::Null && response.result.is_err() =>
+            {
+                Ok(Some(response.clone()))
+            },
+            answer => Err(IoError::new(
+                ErrorKind::InvalidData,
+                format!("Unexpected answer to RPC {}: {:?}", id, answer),
+            )),
+        });
+        match timeout {
+            Some(time) => {
+                let timed_out = Delay::new(Instant::now() + time)
+                    .map(|()| None)
+                    .map_err(IoError::other);
+                let completed = answered
+                    .select(timed_out)
+                    .map(|(r, _)| r)
+                    .map_err(|(e, _)| e);
+                Box::new(completed)
+            },
+            None => Box::new(answered),
+        }
+    }
+    /// Sends a notification.
+    ///
+    /// The returned future resolves once the server accepts it. Any answer from the server is
+    /// ignored.
+    pub fn notify(
+        &self, method: String, params: Option<Params>
+    ) -> Box<dyn Future<Item = (), Error = IoError> + Send> {
+        Box::new(self.send(Message::notification(method, params)).map(|_| ()))
+    }
+}
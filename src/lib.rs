@@ -65,6 +65,7 @@
 //! # use tokio_core::net::TcpListener;
 //! # use tokio_io::AsyncRead;
 //! # use tokio_jsonrpc::{LineCodec, Server, ServerCtl, RpcError, Endpoint};
+//! # use tokio_jsonrpc::message::Params;
 //! # use futures::{Future, Stream};
 //! # use serde_json::Value;
 //! #
@@ -83,7 +84,7 @@
 //!     fn rpc(&self,
 //!            ctl: &ServerCtl,
 //!            method: &str,
-//!            _params: &Option<Value>)
+//!            _params: &Option<Params>)
 //!         -> Option<Self::RpcCallResult> {
 //!         match method {
 //!             // Accept a hello message and finish the greeting
@@ -117,26 +118,51 @@ extern crate serde;
 // We use the json! macro only in the tests
 extern crate bytes;
 extern crate futures;
+#[cfg(feature = "hyper")]
+extern crate hyper;
+#[cfg(all(unix, feature = "uds"))]
+extern crate libc;
 #[macro_use]
 extern crate serde_derive;
 #[cfg_attr(test, macro_use)]
 extern crate serde_json;
+#[cfg(feature = "msgpack")]
+extern crate rmp_serde;
+#[cfg(feature = "cbor")]
+extern crate serde_cbor;
 #[macro_use]
 extern crate slog;
+extern crate tokio;
 extern crate tokio_core;
 extern crate tokio_io;
+#[cfg(feature = "tokio-process")]
+extern crate tokio_process;
+#[cfg(all(unix, feature = "uds"))]
+extern crate tokio_uds;
 extern crate uuid;
+#[cfg(feature = "websocket")]
+extern crate websocket;
 
 pub mod codec;
 pub mod endpoint;
+#[cfg(feature = "hyper")]
+pub mod http;
 pub mod message;
+#[cfg(feature = "tokio-process")]
+pub mod process;
 pub mod server;
+pub mod stdio;
+#[cfg(all(unix, feature = "uds"))]
+pub mod uds;
+#[cfg(feature = "websocket")]
+pub mod ws;
 
 /// This contains some reexports so macros can find them.
 ///
 /// It isn't for the direct use of the library consumer.
 pub mod macro_exports {
-    pub use serde_json::{from_value, Value};
+    pub use serde_json::{from_str, from_value, Value};
+    pub use serde_json::value::RawValue;
     pub use std::option::Option;
     pub use std::result::Result;
 }
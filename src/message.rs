@@ -9,23 +9,91 @@
 //!
 //! The main entrypoint here is the [Message](enum.Message.html). The others are just building
 //! blocks and you should generally work with `Message` instead.
+//!
+//! The older JSON-RPC 1.0 messages are supported too, but they need to be enabled in the
+//! [Parser](struct.Parser.html).
 
-use std::fmt::{Formatter, Result as FmtResult};
+use std::borrow::Cow;
+use std::convert::TryFrom;
+use std::error::Error as StdError;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::mem;
 
-use serde::ser::{Serialize, SerializeStruct, Serializer};
-use serde::de::{Deserialize, Deserializer, Error, Unexpected, Visitor};
-use serde_json::{to_value, Result as JsonResult, Value};
+use serde::ser::{Serialize, SerializeMap, Serializer};
+use serde::de::{Deserialize, DeserializeOwned, Deserializer, Error, MapAccess, SeqAccess,
+                Unexpected, Visitor};
+use serde_json::{from_value, to_value, Map, Result as JsonResult, Value};
+use serde_json::value::RawValue;
 use uuid::Uuid;
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
-struct Version;
+/// A version (dialect) of the protocol.
+///
+/// Each message remembers the version it was received in, so the answers can be sent in the same
+/// one. The messages created by the constructors are in the 2.0 version, but that can be changed
+/// by [`Message::with_version`](enum.Message.html#method.with_version).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Version {
+    /// JSON-RPC 1.0.
+    ///
+    /// There's no `jsonrpc` field, the notifications have a null `id` and the responses carry both
+    /// the `result` and `error`, one of them being null. Parsing it needs to be enabled by
+    /// [`Parser::accept_v1`](struct.Parser.html#method.accept_v1).
+    V1,
+    /// JSON-RPC 2.0.
+    V2,
+}
 
 impl Serialize for Version {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        serializer.serialize_str("2.0")
+        match *self {
+            Version::V1 => serializer.serialize_str("1.0"),
+            Version::V2 => serializer.serialize_str("2.0"),
+        }
+    }
+}
+
+/// The version of messages that don't have the `jsonrpc` field.
+fn missing_version() -> Version {
+    Version::V1
+}
+
+/// The members of the messages defined by the protocol.
+///
+/// These are never considered extensions, even by a lenient parser.
+const PROTOCOL_MEMBERS: &[&str] = &["jsonrpc", "method", "params", "id", "result", "error"];
+
+/// Serialize the version, if the version has it on the wire.
+fn serialize_version<S: SerializeMap>(sub: &mut S, version: Version) -> Result<(), S::Error> {
+    match version {
+        Version::V1 => Ok(()),
+        Version::V2 => sub.serialize_entry("jsonrpc", &version),
+    }
+}
+
+/// Serialize the parts shared by requests and notifications.
+fn serialize_call<S: SerializeMap>(
+    sub: &mut S, version: Version, method: &str, params: &Option<Params>
+) -> Result<(), S::Error> {
+    serialize_version(sub, version)?;
+    sub.serialize_entry("method", method)?;
+    match (version, params.as_ref()) {
+        (_, Some(params)) => sub.serialize_entry("params", params),
+        // The 1.0 requires the params to be always present
+        (Version::V1, None) => sub.serialize_entry("params", &[] as &[Value]),
+        (Version::V2, None) => Ok(()),
     }
 }
 
+/// Serialize the extension members.
+fn serialize_extensions<S: SerializeMap>(
+    sub: &mut S, extensions: &Map<String, Value>
+) -> Result<(), S::Error> {
+    for (name, value) in extensions {
+        sub.serialize_entry(name, value)?;
+    }
+    Ok(())
+}
+
 impl<'de> Deserialize<'de> for Version {
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         struct VersionVisitor;
@@ -38,8 +106,9 @@ impl<'de> Deserialize<'de> for Version {
 
             fn visit_str<E: Error>(self, value: &str) -> Result<Version, E> {
                 match value {
-                    "2.0" => Ok(Version),
-                    _ => Err(E::invalid_value(Unexpected::Str(value), &"value 2.0")),
+                    "1.0" => Ok(Version::V1),
+                    "2.0" => Ok(Version::V2),
+                    _ => Err(E::invalid_value(Unexpected::Str(value), &"\"1.0\" or \"2.0\"")),
                 }
             }
         }
@@ -47,46 +116,433 @@ impl<'de> Deserialize<'de> for Version {
     }
 }
 
+/// An ID of a request.
+///
+/// The specification allows only strings, numbers without a fractional part and null. Anything
+/// else (objects, arrays, booleans, fractional numbers) is refused during deserialization.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Id {
+    /// A numeric ID.
+    Number(i64),
+    /// A numeric ID too large for the `Number` variant.
+    ///
+    /// Only the integers above `i64::MAX` are deserialized into this variant, so each number has
+    /// a single form.
+    Unsigned(u64),
+    /// A string ID.
+    String(String),
+    /// The null ID.
+    ///
+    /// Used in error responses to requests whose ID couldn't be detected. Using it in requests is
+    /// discouraged by the specification.
+    Null,
+}
+
+impl Display for Id {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        match *self {
+            Id::Number(n) => write!(f, "{}", n),
+            Id::Unsigned(n) => write!(f, "{}", n),
+            Id::String(ref s) => write!(f, "{:?}", s),
+            Id::Null => write!(f, "null"),
+        }
+    }
+}
+
+impl From<i64> for Id {
+    fn from(n: i64) -> Self {
+        Id::Number(n)
+    }
+}
+
+impl From<String> for Id {
+    fn from(s: String) -> Self {
+        Id::String(s)
+    }
+}
+
+impl<'a> From<&'a str> for Id {
+    fn from(s: &'a str) -> Self {
+        Id::String(s.to_owned())
+    }
+}
+
+impl Serialize for Id {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match *self {
+            Id::Number(n) => serializer.serialize_i64(n),
+            Id::Unsigned(n) => serializer.serialize_u64(n),
+            Id::String(ref s) => serializer.serialize_str(s),
+            Id::Null => serializer.serialize_unit(),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Id {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct IdVisitor;
+        impl<'de> Visitor<'de> for IdVisitor {
+            type Value = Id;
+
+            fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+                formatter.write_str("a string, an integer or null")
+            }
+
+            fn visit_i64<E: Error>(self, value: i64) -> Result<Id, E> {
+                Ok(Id::Number(value))
+            }
+
+            fn visit_u64<E: Error>(self, value: u64) -> Result<Id, E> {
+                // Anything above i64::MAX wraps around into negative numbers, so it stays unsigned
+                let signed = value as i64;
+                if signed < 0 {
+                    Ok(Id::Unsigned(value))
+                } else {
+                    Ok(Id::Number(signed))
+                }
+            }
+
+            fn visit_str<E: Error>(self, value: &str) -> Result<Id, E> {
+                Ok(Id::String(value.to_owned()))
+            }
+
+            fn visit_string<E: Error>(self, value: String) -> Result<Id, E> {
+                Ok(Id::String(value))
+            }
+
+            fn visit_unit<E: Error>(self) -> Result<Id, E> {
+                Ok(Id::Null)
+            }
+
+            fn visit_none<E: Error>(self) -> Result<Id, E> {
+                Ok(Id::Null)
+            }
+        }
+        deserializer.deserialize_any(IdVisitor)
+    }
+}
+
+/// A source of IDs for the outgoing requests.
+///
+/// The [`Endpoint`](../endpoint/struct.Endpoint.html) uses one to give IDs to the RPCs its clients
+/// call. The IDs must not repeat while the previous request with the same ID is still waiting for
+/// an answer. Any `FnMut() -> Id` closure can be used as a generator too.
+pub trait IdGenerator: Send {
+    /// Produce the next ID.
+    fn next_id(&mut self) -> Id;
+}
+
+impl<F: FnMut() -> Id + Send> IdGenerator for F {
+    fn next_id(&mut self) -> Id {
+        self()
+    }
+}
+
+/// A generator of random UUID (version 4) IDs.
+///
+/// This is the default one. The IDs are strings.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct UuidIds;
+
+impl IdGenerator for UuidIds {
+    fn next_id(&mut self) -> Id {
+        Id::String(Uuid::new_v4().hyphenated().to_string())
+    }
+}
+
+/// A generator of sequential numeric IDs.
+///
+/// It counts up from the provided starting number. The IDs are short and cheap to handle and they
+/// are deterministic, which comes handy in tests.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SequentialIds(i64);
+
+impl SequentialIds {
+    /// A constructor.
+    ///
+    /// The first produced ID is `start`.
+    pub fn new(start: i64) -> Self {
+        SequentialIds(start)
+    }
+}
+
+impl IdGenerator for SequentialIds {
+    fn next_id(&mut self) -> Id {
+        let id = self.0;
+        self.0 = self.0.wrapping_add(1);
+        Id::Number(id)
+    }
+}
+
+/// Parameters of a request or a notification.
+///
+/// The specification allows only an array (parameters passed by position) or an object
+/// (parameters passed by name). Messages with anything else as their parameters are invalid.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Params {
+    /// Parameters passed by position.
+    Positional(Vec<Value>),
+    /// Parameters passed by name.
+    Named(Map<String, Value>),
+}
+
+impl Params {
+    /// The number of parameters.
+    pub fn len(&self) -> usize {
+        match *self {
+            Params::Positional(ref params) => params.len(),
+            Params::Named(ref params) => params.len(),
+        }
+    }
+    /// Checks if there are no parameters (an empty array or object).
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    /// Provides the positional parameters, if they are positional.
+    pub fn as_positional(&self) -> Option<&[Value]> {
+        match *self {
+            Params::Positional(ref params) => Some(params),
+            Params::Named(_) => None,
+        }
+    }
+    /// Provides the named parameters, if they are named.
+    pub fn as_named(&self) -> Option<&Map<String, Value>> {
+        match *self {
+            Params::Positional(_) => None,
+            Params::Named(ref params) => Some(params),
+        }
+    }
+    /// Looks up a positional parameter by its index.
+    ///
+    /// Returns `None` if the parameters are named or if there are not enough of them.
+    pub fn get_index(&self, index: usize) -> Option<&Value> {
+        self.as_positional().and_then(|params| params.get(index))
+    }
+    /// Looks up a named parameter.
+    ///
+    /// Returns `None` if the parameters are positional or the parameter is missing.
+    pub fn get_name(&self, name: &str) -> Option<&Value> {
+        self.as_named().and_then(|params| params.get(name))
+    }
+    /// Converts all the parameters into the given type.
+    ///
+    /// The type is decoded either from the array or the object. A failure is reported as an
+    /// invalid params error, ready to be returned to the caller.
+    pub fn parse<T: DeserializeOwned>(&self) -> Result<T, RpcError> {
+        from_value(self.clone().into())
+            .map_err(|e| RpcError::invalid_params(Some(format!("Incompatible type: {}", e))))
+    }
+}
+
+impl From<Vec<Value>> for Params {
+    fn from(params: Vec<Value>) -> Self {
+        Params::Positional(params)
+    }
+}
+
+impl From<Map<String, Value>> for Params {
+    fn from(params: Map<String, Value>) -> Self {
+        Params::Named(params)
+    }
+}
+
+impl TryFrom<Value> for Params {
+    /// The value is returned back if it is neither an array nor an object.
+    type Error = Value;
+    fn try_from(value: Value) -> Result<Self, Value> {
+        match value {
+            Value::Array(params) => Ok(Params::Positional(params)),
+            Value::Object(params) => Ok(Params::Named(params)),
+            other => Err(other),
+        }
+    }
+}
+
+impl From<Params> for Value {
+    fn from(params: Params) -> Self {
+        match params {
+            Params::Positional(params) => Value::Array(params),
+            Params::Named(params) => Value::Object(params),
+        }
+    }
+}
+
 /// An RPC request.
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[serde(deny_unknown_fields)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct Request {
+    #[serde(default = "missing_version")]
     jsonrpc: Version,
     pub method: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub params: Option<Value>,
-    pub id: Value,
+    pub params: Option<Params>,
+    pub id: Id,
+    /// Additional members of the request, not defined by the protocol.
+    ///
+    /// They are accepted only by a [lenient](struct.Parser.html#method.lenient) parser. Anything
+    /// put in here is sent together with the request.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl Serialize for Request {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut sub = serializer.serialize_map(None)?;
+        serialize_call(&mut sub, self.jsonrpc, &self.method, &self.params)?;
+        sub.serialize_entry("id", &self.id)?;
+        serialize_extensions(&mut sub, &self.extensions)?;
+        sub.end()
+    }
 }
 
 impl Request {
+    /// The version of the protocol the request is in.
+    pub fn version(&self) -> Version {
+        self.jsonrpc
+    }
     /// Answer the request with a (positive) reply.
     ///
-    /// The ID is taken from the request.
+    /// The ID and version are taken from the request.
     pub fn reply(&self, reply: Value) -> Message {
         Message::Response(Response {
-            jsonrpc: Version,
+            jsonrpc: self.jsonrpc,
             result: Ok(reply),
             id: self.id.clone(),
+            extensions: Map::new(),
         })
     }
     /// Answer the request with an error.
     pub fn error(&self, error: RpcError) -> Message {
         Message::Response(Response {
-            jsonrpc: Version,
+            jsonrpc: self.jsonrpc,
             result: Err(error),
             id: self.id.clone(),
+            extensions: Map::new(),
         })
     }
 }
 
+/// A classification of error codes.
+///
+/// The specification defines some error codes and reserves a range for others. This allows
+/// working with them without comparing the magic numbers. It converts from and into the numeric
+/// code.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum ErrorCode {
+    /// Invalid JSON was received (-32700).
+    ParseError,
+    /// The JSON sent is not a valid request object (-32600).
+    InvalidRequest,
+    /// The method does not exist or is not available (-32601).
+    MethodNotFound,
+    /// Invalid method parameters (-32602).
+    InvalidParams,
+    /// Internal JSON-RPC error (-32603).
+    InternalError,
+    /// A code from the range reserved for implementation-defined server errors (-32099 to
+    /// -32000).
+    ServerError(i64),
+    /// A code from the range reserved for pre-defined errors, but not defined (yet).
+    Reserved(i64),
+    /// An application-defined code (anything outside the reserved range).
+    Application(i64),
+}
+
+impl ErrorCode {
+    /// Is it one of the errors defined by the specification?
+    pub fn is_standard(&self) -> bool {
+        matches!(
+            *self,
+            ErrorCode::ParseError
+                | ErrorCode::InvalidRequest
+                | ErrorCode::MethodNotFound
+                | ErrorCode::InvalidParams
+                | ErrorCode::InternalError
+        )
+    }
+    /// Is it from the range of the implementation-defined server errors?
+    pub fn is_server_error(&self) -> bool {
+        matches!(*self, ErrorCode::ServerError(_))
+    }
+    /// Is it an application-defined error?
+    pub fn is_application(&self) -> bool {
+        matches!(*self, ErrorCode::Application(_))
+    }
+    /// The message the specification assigns to the standard errors.
+    ///
+    /// Returns `None` for the non-standard ones.
+    pub fn message(&self) -> Option<&'static str> {
+        match *self {
+            ErrorCode::ParseError => Some("Parse error"),
+            ErrorCode::InvalidRequest => Some("Invalid request"),
+            ErrorCode::MethodNotFound => Some("Method not found"),
+            ErrorCode::InvalidParams => Some("Invalid params"),
+            ErrorCode::InternalError => Some("Internal error"),
+            _ => None,
+        }
+    }
+}
+
+impl From<i64> for ErrorCode {
+    fn from(code: i64) -> Self {
+        match code {
+            -32_700 => ErrorCode::ParseError,
+            -32_600 => ErrorCode::InvalidRequest,
+            -32_601 => ErrorCode::MethodNotFound,
+            -32_602 => ErrorCode::InvalidParams,
+            -32_603 => ErrorCode::InternalError,
+            -32_099..=-32_000 => ErrorCode::ServerError(code),
+            -32_768..=-32_000 => ErrorCode::Reserved(code),
+            _ => ErrorCode::Application(code),
+        }
+    }
+}
+
+impl From<ErrorCode> for i64 {
+    fn from(code: ErrorCode) -> i64 {
+        match code {
+            ErrorCode::ParseError => -32_700,
+            ErrorCode::InvalidRequest => -32_600,
+            ErrorCode::MethodNotFound => -32_601,
+            ErrorCode::InvalidParams => -32_602,
+            ErrorCode::InternalError => -32_603,
+            ErrorCode::ServerError(code)
+            | ErrorCode::Reserved(code)
+            | ErrorCode::Application(code) => code,
+        }
+    }
+}
+
+/// An error returned when trying to create a server error with a code outside of the reserved
+/// range.
+///
+/// It contains the refused code.
+#[derive(Copy, Clone, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct InvalidServerErrorCode(pub i64);
+
+impl Display for InvalidServerErrorCode {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "Code {} is not in the server error range", self.0)
+    }
+}
+
+impl StdError for InvalidServerErrorCode {
+    fn description(&self) -> &str {
+        "Code not in the server error range"
+    }
+}
+
 /// An error code.
 #[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[serde(deny_unknown_fields)]
 pub struct RpcError {
     pub code: i64,
     pub message: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub data: Option<Value>,
+    /// Additional members of the error, not defined by the protocol.
+    ///
+    /// They are accepted only by a [lenient](struct.Parser.html#method.lenient) parser.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
 }
 
 impl RpcError {
@@ -98,36 +554,89 @@ impl RpcError {
             code,
             message,
             data,
+            extensions: Map::new(),
         }
     }
+    /// The classification of the error code.
+    pub fn error_code(&self) -> ErrorCode {
+        self.code.into()
+    }
     /// Create an Invalid Param error.
     pub fn invalid_params(msg: Option<String>) -> Self {
-        RpcError::new(-32_602, "Invalid params".to_owned(), msg.map(Value::String))
+        RpcError::new(
+            ErrorCode::InvalidParams.into(),
+            "Invalid params".to_owned(),
+            msg.map(Value::String),
+        )
     }
     /// Create a server error.
     pub fn server_error<E: Serialize>(e: Option<E>) -> Self {
-        RpcError::new(
-            -32_000,
+        RpcError::server_error_with_code(-32_000, e).expect("-32000 is a server error code")
+    }
+    /// Create a server error with a specific code.
+    ///
+    /// The code must be in the range reserved for the server errors (-32099 to -32000), otherwise
+    /// an error is returned.
+    pub fn server_error_with_code<E: Serialize>(
+        code: i64, e: Option<E>
+    ) -> Result<Self, InvalidServerErrorCode> {
+        if !ErrorCode::from(code).is_server_error() {
+            return Err(InvalidServerErrorCode(code));
+        }
+        Ok(RpcError::new(
+            code,
             "Server error".to_owned(),
             e.map(|v| to_value(v).expect("Must be representable in JSON")),
-        )
+        ))
     }
     /// Create an invalid request error.
     pub fn invalid_request() -> Self {
-        RpcError::new(-32_600, "Invalid request".to_owned(), None)
+        RpcError::new(
+            ErrorCode::InvalidRequest.into(),
+            "Invalid request".to_owned(),
+            None,
+        )
     }
     /// Create a parse error.
     pub fn parse_error(e: String) -> Self {
-        RpcError::new(-32_700, "Parse error".to_owned(), Some(Value::String(e)))
+        RpcError::new(
+            ErrorCode::ParseError.into(),
+            "Parse error".to_owned(),
+            Some(Value::String(e)),
+        )
     }
     /// Create a method not found error.
     pub fn method_not_found(method: String) -> Self {
         RpcError::new(
-            -32_601,
+            ErrorCode::MethodNotFound.into(),
             "Method not found".to_owned(),
             Some(Value::String(method)),
         )
     }
+    /// Create an internal error.
+    pub fn internal_error<E: Serialize>(e: Option<E>) -> Self {
+        RpcError::new(
+            ErrorCode::InternalError.into(),
+            "Internal error".to_owned(),
+            e.map(|v| to_value(v).expect("Must be representable in JSON")),
+        )
+    }
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut Formatter) -> FmtResult {
+        write!(f, "{} ({})", self.message, self.code)?;
+        if let Some(ref data) = self.data {
+            write!(f, ": {}", data)?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for RpcError {
+    fn description(&self) -> &str {
+        &self.message
+    }
 }
 
 /// A response to an RPC.
@@ -137,18 +646,48 @@ impl RpcError {
 pub struct Response {
     jsonrpc: Version,
     pub result: Result<Value, RpcError>,
-    pub id: Value,
+    pub id: Id,
+    /// Additional members of the response, not defined by the protocol.
+    ///
+    /// They are accepted only by a [lenient](struct.Parser.html#method.lenient) parser. Anything
+    /// put in here is sent together with the response.
+    pub extensions: Map<String, Value>,
+}
+
+impl Response {
+    /// A response to the request with the ID, in the version of the request.
+    pub(crate) fn new(jsonrpc: Version, id: Id, result: Result<Value, RpcError>) -> Self {
+        Response {
+            jsonrpc,
+            result,
+            id,
+            extensions: Map::new(),
+        }
+    }
+    /// The version of the protocol the response is in.
+    pub fn version(&self) -> Version {
+        self.jsonrpc
+    }
 }
 
 impl Serialize for Response {
     fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
-        let mut sub = serializer.serialize_struct("Response", 3)?;
-        sub.serialize_field("jsonrpc", &self.jsonrpc)?;
-        match self.result {
-            Ok(ref value) => sub.serialize_field("result", value),
-            Err(ref err) => sub.serialize_field("error", err),
+        let mut sub = serializer.serialize_map(None)?;
+        serialize_version(&mut sub, self.jsonrpc)?;
+        match (self.jsonrpc, self.result.as_ref()) {
+            (Version::V1, Ok(value)) => {
+                sub.serialize_entry("result", value)?;
+                sub.serialize_entry("error", &Value::Null)
+            },
+            (Version::V1, Err(err)) => {
+                sub.serialize_entry("result", &Value::Null)?;
+                sub.serialize_entry("error", err)
+            },
+            (Version::V2, Ok(value)) => sub.serialize_entry("result", value),
+            (Version::V2, Err(err)) => sub.serialize_entry("error", err),
         }?;
-        sub.serialize_field("id", &self.id)?;
+        sub.serialize_entry("id", &self.id)?;
+        serialize_extensions(&mut sub, &self.extensions)?;
         sub.end()
     }
 }
@@ -163,16 +702,16 @@ fn some_value<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Value
 
 /// A helper trick for deserialization.
 #[derive(Deserialize)]
-#[serde(deny_unknown_fields)]
 struct WireResponse {
-    // It is actually used to eat and sanity check the deserialized text
-    #[allow(dead_code)]
+    #[serde(default = "missing_version")]
     jsonrpc: Version,
     // Make sure we accept null as Some(Value::Null), instead of going to None
     #[serde(default, deserialize_with = "some_value")]
     result: Option<Value>,
     error: Option<RpcError>,
-    id: Value,
+    id: Id,
+    #[serde(flatten)]
+    extensions: Map<String, Value>,
 }
 
 // Implementing deserialize is hard. We sidestep the difficulty by deserializing a similar
@@ -182,30 +721,58 @@ impl<'de> Deserialize<'de> for Response {
     #[allow(unreachable_code)] // For that unreachable below
     fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
         let wr: WireResponse = Deserialize::deserialize(deserializer)?;
-        let result = match (wr.result, wr.error) {
-            (Some(res), None) => Ok(res),
-            (None, Some(err)) => Err(err),
+        let result = match (wr.jsonrpc, wr.result, wr.error) {
+            (_, Some(res), None) => Ok(res),
+            (_, None, Some(err)) => Err(err),
+            // The 1.0 always has both, the unused one is null
+            (Version::V1, Some(Value::Null), Some(err)) => Err(err),
             _ => {
                 let err = D::Error::custom("Either 'error' or 'result' is expected, but not both");
                 return Err(err);
             },
         };
         Ok(Response {
-            jsonrpc: Version,
+            jsonrpc: wr.jsonrpc,
             result,
             id: wr.id,
+            extensions: wr.extensions,
         })
     }
 }
 
 /// A notification (doesn't expect an answer).
-#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
-#[serde(deny_unknown_fields)]
+#[derive(Debug, Deserialize, Clone, PartialEq)]
 pub struct Notification {
+    #[serde(default = "missing_version")]
     jsonrpc: Version,
     pub method: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub params: Option<Value>,
+    pub params: Option<Params>,
+    /// Additional members of the notification, not defined by the protocol.
+    ///
+    /// They are accepted only by a [lenient](struct.Parser.html#method.lenient) parser. Anything
+    /// put in here is sent together with the notification.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl Notification {
+    /// The version of the protocol the notification is in.
+    pub fn version(&self) -> Version {
+        self.jsonrpc
+    }
+}
+
+impl Serialize for Notification {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut sub = serializer.serialize_map(None)?;
+        serialize_call(&mut sub, self.jsonrpc, &self.method, &self.params)?;
+        // The 1.0 notifications are requests with null ID
+        if self.jsonrpc == Version::V1 {
+            sub.serialize_entry("id", &Id::Null)?;
+        }
+        serialize_extensions(&mut sub, &self.extensions)?;
+        sub.end()
+    }
 }
 
 /// One message of the JSON RPC protocol.
@@ -249,31 +816,172 @@ pub enum Message {
 impl Message {
     /// A constructor for a request.
     ///
-    /// The ID is auto-generated.
-    pub fn request(method: String, params: Option<Value>) -> Self {
+    /// The ID is auto-generated (it is a random UUID).
+    pub fn request(method: String, params: Option<Params>) -> Self {
+        Self::request_with_id(method, params, UuidIds.next_id())
+    }
+    /// A constructor for a request with an explicit ID.
+    pub fn request_with_id(method: String, params: Option<Params>, id: Id) -> Self {
         Message::Request(Request {
-            jsonrpc: Version,
+            jsonrpc: Version::V2,
             method,
             params,
-            id: Value::String(Uuid::new_v4().hyphenated().to_string()),
+            id,
+            extensions: Map::new(),
         })
     }
     /// Create a top-level error (without an ID).
     pub fn error(error: RpcError) -> Self {
         Message::Response(Response {
-            jsonrpc: Version,
+            jsonrpc: Version::V2,
             result: Err(error),
-            id: Value::Null,
+            id: Id::Null,
+            extensions: Map::new(),
         })
     }
     /// A constructor for a notification.
-    pub fn notification(method: String, params: Option<Value>) -> Self {
+    pub fn notification(method: String, params: Option<Params>) -> Self {
         Message::Notification(Notification {
-            jsonrpc: Version,
+            jsonrpc: Version::V2,
             method,
             params,
+            extensions: Map::new(),
         })
     }
+    /// The version of the protocol the message is in.
+    ///
+    /// Batches and unmatched sub-messages don't have a version on their own, so this returns
+    /// `None` for them.
+    pub fn version(&self) -> Option<Version> {
+        match *self {
+            Message::Request(ref request) => Some(request.jsonrpc),
+            Message::Response(ref response) => Some(response.jsonrpc),
+            Message::Notification(ref notification) => Some(notification.jsonrpc),
+            Message::Batch(_) | Message::UnmatchedSub(_) => None,
+        }
+    }
+    /// The extension members of the message.
+    ///
+    /// Batches and unmatched sub-messages have no extensions, so this returns `None` for them.
+    pub fn extensions(&self) -> Option<&Map<String, Value>> {
+        match *self {
+            Message::Request(ref request) => Some(&request.extensions),
+            Message::Response(ref response) => Some(&response.extensions),
+            Message::Notification(ref notification) => Some(&notification.extensions),
+            Message::Batch(_) | Message::UnmatchedSub(_) => None,
+        }
+    }
+    /// Mutable access to the extension members of the message.
+    ///
+    /// This allows sending additional members (not defined by the protocol) with the message.
+    /// Batches and unmatched sub-messages have no extensions, so this returns `None` for them.
+    pub fn extensions_mut(&mut self) -> Option<&mut Map<String, Value>> {
+        match *self {
+            Message::Request(ref mut request) => Some(&mut request.extensions),
+            Message::Response(ref mut response) => Some(&mut response.extensions),
+            Message::Notification(ref mut notification) => Some(&mut notification.extensions),
+            Message::Batch(_) | Message::UnmatchedSub(_) => None,
+        }
+    }
+    /// Converts the message to the given version of the protocol.
+    ///
+    /// In case of a batch, all its members are converted. Note that the 1.0 version knows no
+    /// batches, so sending them to a 1.0 peer is unlikely to work.
+    pub fn with_version(self, version: Version) -> Self {
+        match self {
+            Message::Request(request) => Message::Request(Request {
+                jsonrpc: version,
+                ..request
+            }),
+            Message::Response(response) => Message::Response(Response {
+                jsonrpc: version,
+                ..response
+            }),
+            Message::Notification(notification) => Message::Notification(Notification {
+                jsonrpc: version,
+                ..notification
+            }),
+            Message::Batch(batch) => Message::Batch(
+                batch
+                    .into_iter()
+                    .map(|sub| sub.with_version(version))
+                    .collect(),
+            ),
+            unmatched @ Message::UnmatchedSub(_) => unmatched,
+        }
+    }
+}
+
+/// Details of invalid JSON.
+///
+/// The line and column (both starting at 1) point to where the problem was detected. The binary
+/// formats of the [codecs](../codec/index.html) have no lines, so they report 0 for both.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxError {
+    /// The description of the problem, as produced by the JSON parser.
+    pub message: String,
+    /// The line of the input with the problem.
+    pub line: usize,
+    /// The column of the input with the problem.
+    pub column: usize,
+}
+
+impl From<::serde_json::Error> for SyntaxError {
+    fn from(e: ::serde_json::Error) -> Self {
+        SyntaxError {
+            message: format!("{}", e),
+            line: e.line(),
+            column: e.column(),
+        }
+    }
+}
+
+impl Display for SyntaxError {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        write!(fmt, "{}", self.message)
+    }
+}
+
+/// An explanation why a valid JSON is not a valid message.
+///
+/// See [Broken::mismatch](enum.Broken.html#method.mismatch).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Mismatch {
+    /// It is not a JSON object (eg. a number inside a batch).
+    NotObject,
+    /// The `jsonrpc` member is missing or doesn't hold a known version.
+    Version,
+    /// The named member of the protocol has a wrong type or value.
+    InvalidMember(String),
+    /// The named member is not defined by the protocol.
+    UnknownMember(String),
+    /// The members are valid, but don't form any message (eg. both `result` and `error`).
+    Shape,
+}
+
+/// A resource limit of the [Parser](struct.Parser.html) a message exceeded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    /// The message is larger than allowed by
+    /// [`Parser::max_size`](struct.Parser.html#method.max_size).
+    Size,
+    /// The message nests deeper than allowed by
+    /// [`Parser::max_depth`](struct.Parser.html#method.max_depth).
+    Depth,
+    /// The batch is longer than allowed by
+    /// [`Parser::max_batch`](struct.Parser.html#method.max_batch).
+    Batch,
+}
+
+impl Display for Limit {
+    fn fmt(&self, fmt: &mut Formatter) -> FmtResult {
+        let description = match *self {
+            Limit::Size => "Message too large",
+            Limit::Depth => "Message nested too deep",
+            Limit::Batch => "Batch too long",
+        };
+        fmt.write_str(description)
+    }
 }
 
 /// A broken message.
@@ -286,55 +994,804 @@ pub enum Broken {
     Unmatched(Value),
     /// Invalid JSON.
     #[serde(skip_deserializing)]
-    SyntaxError(String),
+    SyntaxError(SyntaxError),
+    /// The message exceeded a resource limit of the parser.
+    ///
+    /// The message is not parsed (or is thrown away), so nothing is known about its content.
+    #[serde(skip_deserializing)]
+    LimitExceeded(Limit),
 }
 
 impl Broken {
     /// Generate an appropriate error message.
     ///
     /// The error message for these things are specified in the RFC, so this just creates an error
-    /// with the right values.
+    /// with the right values. If the unmatched message is a request with a valid ID, the error
+    /// carries it, so the other side knows which of its requests got refused.
     pub fn reply(&self) -> Message {
         match *self {
-            Broken::Unmatched(_) => Message::error(RpcError::invalid_request()),
-            Broken::SyntaxError(ref e) => Message::error(RpcError::parse_error(e.clone())),
+            Broken::Unmatched(_) => Message::Response(Response {
+                jsonrpc: Version::V2,
+                result: Err(RpcError::invalid_request()),
+                id: self.id().unwrap_or(Id::Null),
+                extensions: Map::new(),
+            }),
+            Broken::SyntaxError(ref e) => Message::error(RpcError::parse_error(e.message.clone())),
+            Broken::LimitExceeded(limit) => Message::error(RpcError {
+                data: Some(Value::String(limit.to_string())),
+                ..RpcError::invalid_request()
+            }),
         }
     }
+    /// The ID of the unmatched message, if it has a valid one and looks like a request.
+    ///
+    /// The message looks like a request if it has a string `method` and neither `result` nor
+    /// `error`. The ID of a broken response is not recovered, as it belongs to a request of the
+    /// other side ‒ an error with it would look like the answer to that request.
+    pub fn id(&self) -> Option<Id> {
+        match *self {
+            Broken::Unmatched(ref value) => {
+                let request = matches!(value.get("method"), Some(&Value::String(_)))
+                    && value.get("result").is_none()
+                    && value.get("error").is_none();
+                if request {
+                    value.get("id").and_then(|id| Id::deserialize(id).ok())
+                } else {
+                    None
+                }
+            },
+            Broken::SyntaxError(_) | Broken::LimitExceeded(_) => None,
+        }
+    }
+    /// Explains why the unmatched message is not a valid one.
+    ///
+    /// This is only a best-effort guess made by looking at the JSON value after the fact. It
+    /// doesn't know the settings of the parser, so a message refused for the parser's settings
+    /// (eg. extensions with a strict parser) is explained by the first thing it finds suspicious.
+    /// Syntax errors and exceeded limits return `None`, they carry their own details.
+    pub fn mismatch(&self) -> Option<Mismatch> {
+        let object = match *self {
+            Broken::Unmatched(Value::Object(ref object)) => object,
+            Broken::Unmatched(_) => return Some(Mismatch::NotObject),
+            Broken::SyntaxError(_) | Broken::LimitExceeded(_) => return None,
+        };
+        match object.get("jsonrpc") {
+            None => return Some(Mismatch::Version),
+            Some(version) if Version::deserialize(version).is_err() => {
+                return Some(Mismatch::Version)
+            },
+            _ => (),
+        }
+        let invalid = |name: &str, valid: bool| -> Option<Mismatch> {
+            if object.contains_key(name) && !valid {
+                Some(Mismatch::InvalidMember(name.to_owned()))
+            } else {
+                None
+            }
+        };
+        let member = |name: &str| object.get(name).unwrap_or(&Value::Null);
+        invalid("method", member("method").is_string())
+            .or_else(|| invalid("params", Option::<Params>::deserialize(member("params")).is_ok()))
+            .or_else(|| invalid("id", Id::deserialize(member("id")).is_ok()))
+            .or_else(|| invalid("error", RpcError::deserialize(member("error")).is_ok()))
+            .or_else(|| {
+                object
+                    .keys()
+                    .find(|key| !PROTOCOL_MEMBERS.contains(&key.as_str()))
+                    .map(|key| Mismatch::UnknownMember(key.clone()))
+            })
+            .or(Some(Mismatch::Shape))
+    }
 }
 
-/// A trick to easily deserialize and detect valid JSON, but invalid Message.
-#[derive(Deserialize, Debug)]
-#[serde(untagged)]
-pub(crate) enum WireMessage {
-    Message(Message),
-    Broken(Broken),
-}
-
-pub(crate) fn decoded_to_parsed(res: JsonResult<WireMessage>) -> Parsed {
-    println!("decoded_to_parsed {:?}", res);
-    match res {
-        Ok(WireMessage::Message(Message::UnmatchedSub(value))) => Err(Broken::Unmatched(value)),
-        Ok(WireMessage::Message(m)) => Ok(m),
-        Ok(WireMessage::Broken(b)) => Err(b),
-        Err(e) => Err(Broken::SyntaxError(format!("{}", e))),
+/// Turns a message back into JSON, including the unmatched parts of it.
+pub(crate) fn into_value(msg: Message) -> Value {
+    match msg {
+        Message::UnmatchedSub(value) => value,
+        Message::Batch(batch) => Value::Array(batch.into_iter().map(into_value).collect()),
+        other => to_value(other).expect("Must be representable in JSON"),
     }
 }
 
+/// A trick to easily deserialize and detect valid JSON, but invalid Message.
+#[derive(Deserialize, Debug)]
+#[serde(untagged)]
+pub(crate) enum WireMessage {
+    Message(Message),
+    Broken(Broken),
+}
+
 pub type Parsed = Result<Message, Broken>;
 
+/// Settings of how the messages are parsed.
+///
+/// The default parser accepts only the JSON-RPC 2.0 messages. The [codecs](../codec/index.html)
+/// can be created with a differently configured parser.
+///
+/// The parser also holds the resource limits ‒ the size of a message, how deep it nests and how
+/// long a batch may be. The default parser has no limits, which is fine for a trusted peer. When
+/// talking to anyone else, the limits protect from running out of memory. A message exceeding
+/// them is reported as [`Broken::LimitExceeded`](enum.Broken.html#variant.LimitExceeded), or the
+/// codecs fail with an I/O error if the limits are [fatal](#method.fatal_limits).
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Parser {
+    accept_v1: bool,
+    lenient: bool,
+    max_batch: Option<usize>,
+    max_size: Option<usize>,
+    max_depth: Option<usize>,
+    fatal_limits: bool,
+}
+
+impl Parser {
+    /// A constructor of the default parser.
+    pub fn new() -> Self {
+        Parser::default()
+    }
+    /// Sets if the JSON-RPC 1.0 messages are accepted.
+    ///
+    /// By default, they are rejected as not matching the protocol.
+    pub fn accept_v1(self, accept_v1: bool) -> Self {
+        Parser { accept_v1, ..self }
+    }
+    /// Sets if members not defined by the protocol are accepted.
+    ///
+    /// By default, the parser is strict and messages with unknown members are rejected as not
+    /// matching the protocol. A lenient parser keeps them in the `extensions` of the message (or
+    /// the error inside a response). Messages where a member of the protocol appears in a wrong
+    /// place (eg. an `id` in something that'd otherwise be a notification) are refused even by the
+    /// lenient parser.
+    pub fn lenient(self, lenient: bool) -> Self {
+        Parser { lenient, ..self }
+    }
+    /// Sets the maximum number of messages in a batch.
+    ///
+    /// A longer batch is refused as a whole, with an invalid request error. By default, there's no
+    /// limit.
+    pub fn max_batch(self, max_batch: Option<usize>) -> Self {
+        Parser { max_batch, ..self }
+    }
+    /// Sets the maximum size of a message, in bytes.
+    ///
+    /// The size is checked by the codecs too, so they don't buffer more than this of an incomplete
+    /// message (the line based ones throw the rest of the line away, the binary ones skip the rest
+    /// of the frame). By default, there's no limit.
+    pub fn max_size(self, max_size: Option<usize>) -> Self {
+        Parser { max_size, ..self }
+    }
+    /// Sets how deep a message may nest.
+    ///
+    /// Each array and object counts as one level, including the message itself, so a request
+    /// with positional parameters has the depth of 2. The depth is checked before the JSON is
+    /// parsed. The binary codecs rely on the recursion limits of their decoders instead.
+    ///
+    /// By default, only the recursion limit of the JSON parser (128) applies, reported as a syntax
+    /// error.
+    pub fn max_depth(self, max_depth: Option<usize>) -> Self {
+        Parser { max_depth, ..self }
+    }
+    /// Sets if exceeding a limit is fatal for the codecs.
+    ///
+    /// By default, the codecs produce
+    /// [`Broken::LimitExceeded`](enum.Broken.html#variant.LimitExceeded), which gets answered by
+    /// an error and the connection goes on. If set, the codecs return an I/O error instead, which
+    /// terminates the connection.
+    pub fn fatal_limits(self, fatal_limits: bool) -> Self {
+        Parser {
+            fatal_limits,
+            ..self
+        }
+    }
+    /// Read a [Message](enum.Message.html) from a slice.
+    ///
+    /// Invalid JSON or JSONRPC messages are reported as [Broken](enum.Broken.html).
+    pub fn parse_slice(&self, s: &[u8]) -> Parsed {
+        self.check_input(s)?;
+        self.decoded_to_parsed(::serde_json::de::from_slice(s))
+    }
+    /// Read a [Message](enum.Message.html) from a string.
+    ///
+    /// Invalid JSON or JSONRPC messages are reported as [Broken](enum.Broken.html).
+    pub fn parse_str(&self, s: &str) -> Parsed {
+        self.parse_slice(s.as_bytes())
+    }
+    pub(crate) fn decoded_to_parsed<E>(&self, res: Result<WireMessage, E>) -> Parsed
+    where
+        E: Debug + Into<SyntaxError>,
+    {
+        let parsed = match res {
+            Ok(WireMessage::Message(Message::Batch(ref batch)))
+                if self.batch_too_long(batch.len()) =>
+            {
+                Err(Broken::LimitExceeded(Limit::Batch))
+            },
+            Ok(WireMessage::Message(m)) => Ok(self.check(m)),
+            Ok(WireMessage::Broken(b)) => Err(b),
+            Err(e) => Err(Broken::SyntaxError(e.into())),
+        };
+        match parsed {
+            Ok(Message::UnmatchedSub(value)) => Err(Broken::Unmatched(value)),
+            other => other,
+        }
+    }
+    /// Checks if the message is allowed by the settings.
+    ///
+    /// The deserialization itself is permissive, so this turns the messages not allowed by the
+    /// settings (1.0 ones, ones with extensions) into unmatched ones. It also turns the 1.0
+    /// notifications (which look like requests with null ID) into real notifications.
+    ///
+    /// Empty batches are unmatched as a whole, nested batches are unmatched inside the outer one.
+    fn check(&self, msg: Message) -> Message {
+        match msg {
+            Message::Batch(batch) => {
+                if batch.is_empty() {
+                    return Message::UnmatchedSub(into_value(Message::Batch(batch)));
+                }
+                let batch = batch
+                    .into_iter()
+                    .map(|sub| match sub {
+                        nested @ Message::Batch(_) => Message::UnmatchedSub(into_value(nested)),
+                        sub => self.check(sub),
+                    })
+                    .collect();
+                Message::Batch(batch)
+            },
+            ref other if !self.allowed(other) => {
+                Message::UnmatchedSub(to_value(other).expect("Must be representable in JSON"))
+            },
+            Message::Request(Request {
+                jsonrpc: Version::V1,
+                method,
+                params,
+                id: Id::Null,
+                extensions,
+            }) => Message::Notification(Notification {
+                jsonrpc: Version::V1,
+                method,
+                params,
+                extensions,
+            }),
+            other => other,
+        }
+    }
+    /// Checks the size and nesting of a serialized message, before it is parsed.
+    pub(crate) fn check_input(&self, s: &[u8]) -> Result<(), Broken> {
+        if self.size_exceeded(s.len()) {
+            Err(Broken::LimitExceeded(Limit::Size))
+        } else if self.max_depth.into_iter().any(|max| too_deep(s, max)) {
+            Err(Broken::LimitExceeded(Limit::Depth))
+        } else {
+            Ok(())
+        }
+    }
+    pub(crate) fn size_exceeded(&self, size: usize) -> bool {
+        self.max_size.into_iter().any(|max| size > max)
+    }
+    pub(crate) fn limits_fatal(&self) -> bool {
+        self.fatal_limits
+    }
+    fn batch_too_long(&self, len: usize) -> bool {
+        self.max_batch.into_iter().any(|max| len > max)
+    }
+    fn allowed(&self, msg: &Message) -> bool {
+        if msg.version() == Some(Version::V1) && !self.accept_v1 {
+            return false;
+        }
+        let extensions = msg.extensions();
+        let misplaced = extensions.into_iter().any(|extensions| {
+            PROTOCOL_MEMBERS
+                .iter()
+                .any(|member| extensions.contains_key(*member))
+        });
+        let error_extensions = match *msg {
+            Message::Response(Response {
+                result: Err(ref err),
+                ..
+            }) => Some(&err.extensions),
+            _ => None,
+        };
+        let extended = extensions
+            .into_iter()
+            .chain(error_extensions)
+            .any(|extensions| !extensions.is_empty());
+        !misplaced && (self.lenient || !extended)
+    }
+}
+
+/// Checks if the JSON nests deeper than `max_depth`.
+///
+/// This only counts the brackets outside of strings, without parsing anything, so it is safe to
+/// run on any input. It stops at the end of the first value.
+fn too_deep(input: &[u8], max_depth: usize) -> bool {
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    for &byte in input {
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match byte {
+            b'"' => in_string = true,
+            b'[' | b'{' => {
+                depth += 1;
+                if depth > max_depth {
+                    return true;
+                }
+            },
+            // The end of the first value (or a stray bracket the parser refuses later)
+            b']' | b'}' if depth <= 1 => return false,
+            b']' | b'}' => depth -= 1,
+            _ => (),
+        }
+    }
+    false
+}
+
 /// Read a [Message](enum.Message.html) from a slice.
 ///
-/// Invalid JSON or JSONRPC messages are reported as [Broken](enum.Broken.html).
+/// Invalid JSON or JSONRPC messages are reported as [Broken](enum.Broken.html). Only the JSON-RPC
+/// 2.0 messages are accepted, use a [Parser](struct.Parser.html) for more options.
 pub fn from_slice(s: &[u8]) -> Parsed {
-    decoded_to_parsed(::serde_json::de::from_slice(s))
+    Parser::new().parse_slice(s)
 }
 
 /// Read a [Message](enum.Message.html) from a string.
 ///
-/// Invalid JSON or JSONRPC messages are reported as [Broken](enum.Broken.html).
+/// Invalid JSON or JSONRPC messages are reported as [Broken](enum.Broken.html). Only the JSON-RPC
+/// 2.0 messages are accepted, use a [Parser](struct.Parser.html) for more options.
 pub fn from_str(s: &str) -> Parsed {
-    println!("Received {:?}", s);
-    from_slice(s.as_bytes())
+    Parser::new().parse_str(s)
+}
+
+/// A borrowed request.
+///
+/// Like [Request](struct.Request.html), but the method and parameters point into the buffer the
+/// message was parsed from. See [MessageRef](enum.MessageRef.html).
+#[derive(Debug, Clone)]
+pub struct RequestRef<'a> {
+    jsonrpc: Version,
+    pub method: Cow<'a, str>,
+    pub params: Option<&'a RawValue>,
+    pub id: Id,
+}
+
+impl<'a> RequestRef<'a> {
+    /// The version of the protocol the request is in.
+    pub fn version(&self) -> Version {
+        self.jsonrpc
+    }
+    /// Deserializes the parameters into the given type.
+    ///
+    /// Returns `None` if the request has no parameters.
+    pub fn params<T: Deserialize<'a>>(&self) -> Option<JsonResult<T>> {
+        self.params.map(deserialize_raw)
+    }
+    /// Creates an owned copy of the parameters.
+    pub fn to_params(&self) -> Option<Params> {
+        self.params.map(to_params)
+    }
+    /// Answer the request with a (positive) reply.
+    ///
+    /// The ID and version are taken from the request.
+    pub fn reply(&self, reply: Value) -> Message {
+        Message::Response(Response {
+            jsonrpc: self.jsonrpc,
+            result: Ok(reply),
+            id: self.id.clone(),
+            extensions: Map::new(),
+        })
+    }
+    /// Answer the request with an error.
+    pub fn error(&self, error: RpcError) -> Message {
+        Message::Response(Response {
+            jsonrpc: self.jsonrpc,
+            result: Err(error),
+            id: self.id.clone(),
+            extensions: Map::new(),
+        })
+    }
+}
+
+/// A borrowed notification.
+///
+/// Like [Notification](struct.Notification.html), but the method and parameters point into the
+/// buffer the message was parsed from. See [MessageRef](enum.MessageRef.html).
+#[derive(Debug, Clone)]
+pub struct NotificationRef<'a> {
+    jsonrpc: Version,
+    pub method: Cow<'a, str>,
+    pub params: Option<&'a RawValue>,
+}
+
+impl<'a> NotificationRef<'a> {
+    /// The version of the protocol the notification is in.
+    pub fn version(&self) -> Version {
+        self.jsonrpc
+    }
+    /// Deserializes the parameters into the given type.
+    ///
+    /// Returns `None` if the notification has no parameters.
+    pub fn params<T: Deserialize<'a>>(&self) -> Option<JsonResult<T>> {
+        self.params.map(deserialize_raw)
+    }
+    /// Creates an owned copy of the parameters.
+    pub fn to_params(&self) -> Option<Params> {
+        self.params.map(to_params)
+    }
+}
+
+/// A borrowed response.
+///
+/// Like [Response](struct.Response.html), but a successful result points into the buffer the
+/// message was parsed from. See [MessageRef](enum.MessageRef.html).
+#[derive(Debug, Clone)]
+pub struct ResponseRef<'a> {
+    jsonrpc: Version,
+    pub result: Result<&'a RawValue, RpcError>,
+    pub id: Id,
+}
+
+impl<'a> ResponseRef<'a> {
+    /// The version of the protocol the response is in.
+    pub fn version(&self) -> Version {
+        self.jsonrpc
+    }
+    /// Creates an owned copy of the response.
+    pub fn to_response(&self) -> Response {
+        let result = self.result.clone().map(unmatched);
+        Response::new(self.jsonrpc, self.id.clone(), result)
+    }
+}
+
+/// A message borrowing from the buffer it was parsed from.
+///
+/// This is the zero-copy variant of [Message](enum.Message.html). Instead of building owned
+/// strings and `Value` trees, the method is borrowed (unless it contains escape sequences) and
+/// the parameters and results are kept as raw JSON. They can then be deserialized directly into
+/// the types the application needs, without the intermediate `Value`.
+///
+/// It is produced by [`Parser::parse_ref`](struct.Parser.html#method.parse_ref), in a single pass
+/// over the input. The endpoint uses it for connections providing raw frames and the server gets
+/// the borrowed requests through
+/// [`Server::rpc_ref`](../server/trait.Server.html#method.rpc_ref). The borrowed messages don't
+/// keep any extensions, a lenient parser simply skips them. They can be turned into the owned
+/// ones by [`to_message`](#method.to_message).
+#[derive(Debug, Clone)]
+pub enum MessageRef<'a> {
+    /// An RPC request.
+    Request(RequestRef<'a>),
+    /// A response to a Request.
+    Response(ResponseRef<'a>),
+    /// A notification.
+    Notification(NotificationRef<'a>),
+    /// A batch of more requests or responses.
+    Batch(Vec<MessageRef<'a>>),
+    /// An unmatched sub entry in a `Batch`.
+    ///
+    /// The same as [`Message::UnmatchedSub`](enum.Message.html#variant.UnmatchedSub).
+    UnmatchedSub(Value),
+}
+
+impl<'a> MessageRef<'a> {
+    /// The version of the protocol the message is in.
+    ///
+    /// Batches and unmatched sub-messages don't have a version on their own, so this returns
+    /// `None` for them.
+    pub fn version(&self) -> Option<Version> {
+        match *self {
+            MessageRef::Request(ref request) => Some(request.jsonrpc),
+            MessageRef::Response(ref response) => Some(response.jsonrpc),
+            MessageRef::Notification(ref notification) => Some(notification.jsonrpc),
+            MessageRef::Batch(_) | MessageRef::UnmatchedSub(_) => None,
+        }
+    }
+    /// Creates an owned copy of the message.
+    pub fn to_message(&self) -> Message {
+        match *self {
+            MessageRef::Request(ref request) => Message::Request(Request {
+                jsonrpc: request.jsonrpc,
+                method: request.method.clone().into_owned(),
+                params: request.to_params(),
+                id: request.id.clone(),
+                extensions: Map::new(),
+            }),
+            MessageRef::Response(ref response) => Message::Response(response.to_response()),
+            MessageRef::Notification(ref notification) => Message::Notification(Notification {
+                jsonrpc: notification.jsonrpc,
+                method: notification.method.clone().into_owned(),
+                params: notification.to_params(),
+                extensions: Map::new(),
+            }),
+            MessageRef::Batch(ref batch) => {
+                Message::Batch(batch.iter().map(MessageRef::to_message).collect())
+            },
+            MessageRef::UnmatchedSub(ref value) => Message::UnmatchedSub(value.clone()),
+        }
+    }
+}
+
+/// A result of parsing a borrowed message.
+pub type ParsedRef<'a> = Result<MessageRef<'a>, Broken>;
+
+fn deserialize_raw<'a, T: Deserialize<'a>>(raw: &'a RawValue) -> JsonResult<T> {
+    ::serde_json::from_str(raw.get())
+}
+
+/// Turns raw parameters into owned ones.
+fn to_params(raw: &RawValue) -> Params {
+    deserialize_raw(raw).expect("Checked to be an array or object when parsing")
+}
+
+/// A string that borrows from the input if it can.
+#[derive(Deserialize)]
+struct BorrowedStr<'a>(#[serde(borrow)] Cow<'a, str>);
+
+/// The members of an object, as they came, for turning an unmatched message into a `Value`.
+type RawMembers<'a> = Vec<(Cow<'a, str>, &'a RawValue)>;
+
+/// All the members a message may have, before deciding what kind of message it is.
+///
+/// The members are interpreted as they come, but a member of a wrong type or a duplicate one
+/// doesn't fail the deserialization, it only marks the message invalid. Therefore one broken
+/// sub-message doesn't fail a whole batch.
+#[derive(Default)]
+struct WireRef<'a> {
+    jsonrpc: Option<Version>,
+    method: Option<Cow<'a, str>>,
+    params: Option<&'a RawValue>,
+    id: Option<Id>,
+    result: Option<&'a RawValue>,
+    error: Option<RpcError>,
+    unknown: bool,
+    invalid: bool,
+    members: RawMembers<'a>,
+}
+
+impl<'a> WireRef<'a> {
+    /// Interprets one member of the object.
+    fn member(&mut self, name: Cow<'a, str>, raw: &'a RawValue) {
+        /// Fills the slot, if the value is valid and the slot is not filled already.
+        fn set<T>(slot: &mut Option<T>, value: Option<T>) -> bool {
+            match value {
+                Some(value) if slot.is_none() => {
+                    *slot = Some(value);
+                    true
+                },
+                _ => false,
+            }
+        }
+
+        let null = raw.get() == "null";
+        let valid = match &*name {
+            "jsonrpc" => set(&mut self.jsonrpc, deserialize_raw(raw).ok()),
+            "method" => {
+                let method = deserialize_raw(raw).ok().map(|method: BorrowedStr| method.0);
+                set(&mut self.method, method)
+            },
+            // Null params are the same as missing ones, as in the owned messages
+            "params" if null => true,
+            "params" => set(&mut self.params, Some(raw)),
+            "id" => set(&mut self.id, deserialize_raw(raw).ok()),
+            // A null result is a valid result (unlike a null error)
+            "result" => set(&mut self.result, Some(raw)),
+            "error" if null => true,
+            "error" => set(&mut self.error, deserialize_raw(raw).ok()),
+            _ => {
+                self.unknown = true;
+                true
+            },
+        };
+        self.invalid |= !valid;
+        self.members.push((name, raw));
+    }
+}
+
+/// Turns the members of an unmatched message into a value.
+fn members_value(members: RawMembers) -> Value {
+    let members = members
+        .into_iter()
+        .map(|(name, raw)| (name.into_owned(), unmatched(raw)))
+        .collect();
+    Value::Object(members)
+}
+
+/// Turns a raw value into a value for the unmatched message.
+fn unmatched(raw: &RawValue) -> Value {
+    deserialize_raw(raw).expect("Raw values are valid JSON")
+}
+
+/// The whole input or an item of a batch, deserialized in a single pass.
+enum EntryRef<'a> {
+    /// An object, possibly a message.
+    Object(WireRef<'a>),
+    /// An array, a batch at the top level.
+    Array(Vec<EntryRef<'a>>),
+    /// Anything else, never a message.
+    Other(Value),
+}
+
+impl<'a> EntryRef<'a> {
+    /// Turns an unmatched entry into a value.
+    fn into_value(self) -> Value {
+        match self {
+            EntryRef::Object(wire) => members_value(wire.members),
+            EntryRef::Array(entries) => {
+                Value::Array(entries.into_iter().map(EntryRef::into_value).collect())
+            },
+            EntryRef::Other(value) => value,
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for EntryRef<'de> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct EntryVisitor;
+        impl<'de> Visitor<'de> for EntryVisitor {
+            type Value = EntryRef<'de>;
+
+            fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+                formatter.write_str("any JSON value")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<EntryRef<'de>, A::Error> {
+                let mut wire = WireRef::default();
+                while let Some(name) = map.next_key::<BorrowedStr>()? {
+                    wire.member(name.0, map.next_value()?);
+                }
+                Ok(EntryRef::Object(wire))
+            }
+            fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<EntryRef<'de>, A::Error> {
+                let mut entries = Vec::new();
+                while let Some(entry) = seq.next_element()? {
+                    entries.push(entry);
+                }
+                Ok(EntryRef::Array(entries))
+            }
+            fn visit_unit<E: Error>(self) -> Result<EntryRef<'de>, E> {
+                Ok(EntryRef::Other(Value::Null))
+            }
+            fn visit_bool<E: Error>(self, v: bool) -> Result<EntryRef<'de>, E> {
+                Ok(EntryRef::Other(Value::Bool(v)))
+            }
+            fn visit_i64<E: Error>(self, v: i64) -> Result<EntryRef<'de>, E> {
+                Ok(EntryRef::Other(Value::from(v)))
+            }
+            fn visit_u64<E: Error>(self, v: u64) -> Result<EntryRef<'de>, E> {
+                Ok(EntryRef::Other(Value::from(v)))
+            }
+            fn visit_f64<E: Error>(self, v: f64) -> Result<EntryRef<'de>, E> {
+                Ok(EntryRef::Other(Value::from(v)))
+            }
+            fn visit_str<E: Error>(self, v: &str) -> Result<EntryRef<'de>, E> {
+                Ok(EntryRef::Other(Value::String(v.to_owned())))
+            }
+        }
+        deserializer.deserialize_any(EntryVisitor)
+    }
+}
+
+impl Parser {
+    /// Read a borrowed [MessageRef](enum.MessageRef.html) from a slice.
+    ///
+    /// This follows the same rules as [parse_slice](#method.parse_slice), but the parsed message
+    /// borrows from the input instead of building owned values. The input is parsed only once,
+    /// the parameters and results are just delimited, to be deserialized later.
+    pub fn parse_ref<'a>(&self, s: &'a [u8]) -> ParsedRef<'a> {
+        self.check_input(s)?;
+        let entry = ::serde_json::from_slice(s).map_err(|e| Broken::SyntaxError(e.into()))?;
+        match entry {
+            EntryRef::Array(ref subs) if subs.is_empty() => {
+                Err(Broken::Unmatched(Value::Array(Vec::new())))
+            },
+            EntryRef::Array(ref subs) if self.batch_too_long(subs.len()) => {
+                Err(Broken::LimitExceeded(Limit::Batch))
+            },
+            // Nested batches are refused by parse_single, as they are not objects
+            EntryRef::Array(subs) => {
+                let batch = subs.into_iter()
+                    .map(|sub| {
+                        self.parse_single(sub)
+                            .unwrap_or_else(MessageRef::UnmatchedSub)
+                    })
+                    .collect();
+                Ok(MessageRef::Batch(batch))
+            },
+            single => self.parse_single(single).map_err(Broken::Unmatched),
+        }
+    }
+    /// Makes a message out of the entry, or returns it as a value if it is unmatched.
+    fn parse_single<'a>(&self, entry: EntryRef<'a>) -> Result<MessageRef<'a>, Value> {
+        let mut wire = match entry {
+            EntryRef::Object(wire) => wire,
+            other => return Err(other.into_value()),
+        };
+        let members = mem::take(&mut wire.members);
+        let unmatched = move || members_value(members);
+        let jsonrpc = wire.jsonrpc.unwrap_or_else(missing_version);
+        let error_extended = wire.error
+            .iter()
+            .any(|error| !error.extensions.is_empty());
+        // Only arrays and objects are valid parameters
+        let params_invalid = wire.params
+            .into_iter()
+            .any(|params| !params.get().starts_with(&['[', '{'][..]));
+        if wire.invalid || params_invalid || (jsonrpc == Version::V1 && !self.accept_v1)
+            || (!self.lenient && (wire.unknown || error_extended))
+        {
+            return Err(unmatched());
+        }
+        let msg = match wire {
+            WireRef {
+                method: Some(method),
+                params,
+                id: Some(Id::Null),
+                result: None,
+                error: None,
+                ..
+            } if jsonrpc == Version::V1 => Some(MessageRef::Notification(NotificationRef {
+                jsonrpc,
+                method,
+                params,
+            })),
+            WireRef {
+                method: Some(method),
+                params,
+                id: Some(id),
+                result: None,
+                error: None,
+                ..
+            } => Some(MessageRef::Request(RequestRef {
+                jsonrpc,
+                method,
+                params,
+                id,
+            })),
+            WireRef {
+                method: Some(method),
+                params,
+                id: None,
+                result: None,
+                error: None,
+                ..
+            } => Some(MessageRef::Notification(NotificationRef {
+                jsonrpc,
+                method,
+                params,
+            })),
+            WireRef {
+                method: None,
+                params: None,
+                id: Some(id),
+                result,
+                error,
+                ..
+            } => {
+                let result = match (jsonrpc, result, error) {
+                    (_, Some(res), None) => Some(Ok(res)),
+                    (_, None, Some(err)) => Some(Err(err)),
+                    // The 1.0 always has both, the unused one is null
+                    (Version::V1, Some(res), Some(err)) if res.get() == "null" => Some(Err(err)),
+                    _ => None,
+                };
+                result.map(|result| {
+                    MessageRef::Response(ResponseRef {
+                        jsonrpc,
+                        result,
+                        id,
+                    })
+                })
+            },
+            _ => None,
+        };
+        msg.ok_or_else(unmatched)
+    }
 }
 
 impl Into<String> for Message {
@@ -352,7 +1809,7 @@ impl Into<Vec<u8>> for Message {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use serde_json::Value;
+    use serde_json::{to_value, Value};
     use serde_json::ser::to_vec;
     use serde_json::de::from_slice;
 
@@ -376,56 +1833,62 @@ mod tests {
         one(
             r#"{"jsonrpc": "2.0", "method": "call", "id": 1}"#,
             &Message::Request(Request {
-                jsonrpc: Version,
+                jsonrpc: Version::V2,
                 method: "call".to_owned(),
                 params: None,
-                id: json!(1),
+                id: Id::Number(1),
+                extensions: Map::new(),
             }),
         );
         // A request with parameters
         one(
             r#"{"jsonrpc": "2.0", "method": "call", "params": [1, 2, 3], "id": 2}"#,
             &Message::Request(Request {
-                jsonrpc: Version,
+                jsonrpc: Version::V2,
                 method: "call".to_owned(),
-                params: Some(json!([1, 2, 3])),
-                id: json!(2),
+                params: Params::try_from(json!([1, 2, 3])).ok(),
+                id: Id::Number(2),
+                extensions: Map::new(),
             }),
         );
         // A notification (with parameters)
         one(
             r#"{"jsonrpc": "2.0", "method": "notif", "params": {"x": "y"}}"#,
             &Message::Notification(Notification {
-                jsonrpc: Version,
+                jsonrpc: Version::V2,
                 method: "notif".to_owned(),
-                params: Some(json!({"x": "y"})),
+                params: Params::try_from(json!({"x": "y"})).ok(),
+                extensions: Map::new(),
             }),
         );
         // A successful response
         one(
             r#"{"jsonrpc": "2.0", "result": 42, "id": 3}"#,
             &Message::Response(Response {
-                jsonrpc: Version,
+                jsonrpc: Version::V2,
                 result: Ok(json!(42)),
-                id: json!(3),
+                id: Id::Number(3),
+                extensions: Map::new(),
             }),
         );
         // A successful response
         one(
             r#"{"jsonrpc": "2.0", "result": null, "id": 3}"#,
             &Message::Response(Response {
-                jsonrpc: Version,
+                jsonrpc: Version::V2,
                 result: Ok(Value::Null),
-                id: json!(3),
+                id: Id::Number(3),
+                extensions: Map::new(),
             }),
         );
         // An error
         one(
             r#"{"jsonrpc": "2.0", "error": {"code": 42, "message": "Wrong!"}, "id": null}"#,
             &Message::Response(Response {
-                jsonrpc: Version,
+                jsonrpc: Version::V2,
                 result: Err(RpcError::new(42, "Wrong!".to_owned(), None)),
-                id: Value::Null,
+                id: Id::Null,
+                extensions: Map::new(),
             }),
         );
         // A batch
@@ -436,15 +1899,17 @@ mod tests {
             ]"#,
             &Message::Batch(vec![
                 Message::Notification(Notification {
-                    jsonrpc: Version,
+                    jsonrpc: Version::V2,
                     method: "notif".to_owned(),
                     params: None,
+                    extensions: Map::new(),
                 }),
                 Message::Request(Request {
-                    jsonrpc: Version,
+                    jsonrpc: Version::V2,
                     method: "call".to_owned(),
                     params: None,
-                    id: json!(42),
+                    id: Id::Number(42),
+                    extensions: Map::new(),
                 }),
             ]),
         );
@@ -459,15 +1924,17 @@ mod tests {
         assert_eq!(
             Message::Batch(vec![
                 Message::Notification(Notification {
-                    jsonrpc: Version,
+                    jsonrpc: Version::V2,
                     method: "notif".to_owned(),
                     params: None,
+                    extensions: Map::new(),
                 }),
                 Message::Request(Request {
-                    jsonrpc: Version,
+                    jsonrpc: Version::V2,
                     method: "call".to_owned(),
                     params: None,
-                    id: json!(42),
+                    id: Id::Number(42),
+                    extensions: Map::new(),
                 }),
                 Message::UnmatchedSub(Value::Bool(true)),
             ]),
@@ -476,6 +1943,303 @@ mod tests {
         to_vec(&Message::UnmatchedSub(Value::Null)).unwrap_err();
     }
 
+    /// Test the accepted and rejected forms of request IDs and the ID generators
+    #[test]
+    fn ids() {
+        assert_eq!(Id::Number(-5), from_slice::<Id>(b"-5").unwrap());
+        assert_eq!(Id::String("x".to_owned()), from_slice::<Id>(br#""x""#).unwrap());
+        assert_eq!(Id::Null, from_slice::<Id>(b"null").unwrap());
+        // The integers above i64::MAX are valid too
+        assert_eq!(
+            Id::Number(9_223_372_036_854_775_807),
+            from_slice::<Id>(b"9223372036854775807").unwrap()
+        );
+        assert_eq!(
+            Id::Unsigned(18_446_744_073_709_551_615),
+            from_slice::<Id>(b"18446744073709551615").unwrap()
+        );
+        // Fractions, compounds, booleans and too large numbers are not valid IDs
+        for wrong in &["1.5", "[1]", r#"{"x": 1}"#, "true", "18446744073709551616"] {
+            from_slice::<Id>(wrong.as_bytes()).unwrap_err();
+        }
+        // They are serialized back the same way
+        assert_eq!(b"42".to_vec(), to_vec(&Id::Number(42)).unwrap());
+        assert_eq!(
+            b"18446744073709551615".to_vec(),
+            to_vec(&Id::Unsigned(18_446_744_073_709_551_615)).unwrap()
+        );
+        assert_eq!(br#""42""#.to_vec(), to_vec(&Id::from("42")).unwrap());
+        assert_eq!(b"null".to_vec(), to_vec(&Id::Null).unwrap());
+
+        let mut seq = SequentialIds::new(1);
+        assert_eq!(Id::Number(1), seq.next_id());
+        assert_eq!(Id::Number(2), seq.next_id());
+        let mut closure = || Id::from("const");
+        assert_eq!(Id::from("const"), closure.next_id());
+    }
+
+    /// The JSON-RPC 1.0 messages are accepted only when enabled and are answered in 1.0.
+    #[test]
+    fn v1() {
+        let parser = Parser::new().accept_v1(true);
+        let request = r#"{"method": "call", "params": [1], "id": 1}"#;
+        let notification = r#"{"method": "notif", "params": [], "id": null}"#;
+        let response = r#"{"result": 42, "error": null, "id": 1}"#;
+        let error = r#"{"result": null, "error": {"code": 42, "message": "!"}, "id": 1}"#;
+        // By default, these are not recognized
+        for msg in &[request, notification, response, error] {
+            match from_str(msg) {
+                Err(Broken::Unmatched(_)) => (),
+                other => panic!("{} recognized: {:?}", msg, other),
+            }
+        }
+        // But they are with the parser configured
+        let req = match parser.parse_str(request).unwrap() {
+            Message::Request(req) => req,
+            other => panic!("Not a request: {:?}", other),
+        };
+        assert_eq!(Version::V1, req.version());
+        assert_eq!(Id::Number(1), req.id);
+        // And the reply is in the same version
+        assert_eq!(
+            json!({"result": [1], "error": null, "id": 1}),
+            to_value(req.reply(json!([1]))).unwrap()
+        );
+        assert_eq!(
+            Message::notification("notif".to_owned(), Some(Params::Positional(Vec::new())))
+                .with_version(Version::V1),
+            parser.parse_str(notification).unwrap()
+        );
+        match parser.parse_str(response).unwrap() {
+            Message::Response(ref resp) => assert_eq!(Ok(json!(42)), resp.result),
+            other => panic!("Not a response: {:?}", other),
+        }
+        match parser.parse_str(error).unwrap() {
+            Message::Response(ref resp) => assert_eq!(42, resp.result.as_ref().unwrap_err().code),
+            other => panic!("Not a response: {:?}", other),
+        }
+        // The 2.0 ones still work the same
+        assert_eq!(
+            Message::notification("notif".to_owned(), None),
+            parser
+                .parse_str(r#"{"jsonrpc": "2.0", "method": "notif"}"#)
+                .unwrap()
+        );
+        // Mixing the two ways of a response is still wrong
+        let mixed = json!({
+            "jsonrpc": "2.0",
+            "result": null,
+            "error": {"code": 42, "message": "!"},
+            "id": 1,
+        });
+        parser.parse_str(&mixed.to_string()).unwrap_err();
+        // The 1.0 notifications are serialized with the null ID and params
+        assert_eq!(
+            json!({"method": "notif", "params": [], "id": null}),
+            to_value(Message::notification("notif".to_owned(), None).with_version(Version::V1))
+                .unwrap()
+        );
+    }
+
+    /// Unknown members are kept by a lenient parser and sent out again.
+    #[test]
+    fn extensions() {
+        let parser = Parser::new().lenient(true);
+        let input = r#"{"jsonrpc": "2.0", "method": "notif", "meta": {"x": 1}, "trace": "abc"}"#;
+        // The strict one refuses it
+        match from_str(input) {
+            Err(Broken::Unmatched(_)) => (),
+            other => panic!("Extension accepted: {:?}", other),
+        }
+        let mut msg = parser.parse_str(input).unwrap();
+        {
+            let extensions = msg.extensions().unwrap();
+            assert_eq!(2, extensions.len());
+            assert_eq!(json!({"x": 1}), extensions["meta"]);
+            assert_eq!(json!("abc"), extensions["trace"]);
+        }
+        msg.extensions_mut()
+            .unwrap()
+            .insert("another".to_owned(), json!(true));
+        assert_eq!(
+            json!({
+                "jsonrpc": "2.0",
+                "method": "notif",
+                "meta": {"x": 1},
+                "trace": "abc",
+                "another": true,
+            }),
+            to_value(&msg).unwrap()
+        );
+        // Extensions inside the error object
+        let error = r#"{"jsonrpc": "2.0", "error": {"code": 1, "message": "!", "at": 2}, "id": 1}"#;
+        from_str(error).unwrap_err();
+        match parser.parse_str(error).unwrap() {
+            Message::Response(Response {
+                result: Err(ref err),
+                ..
+            }) => assert_eq!(json!(2), err.extensions["at"]),
+            other => panic!("Not an error response: {:?}", other),
+        }
+        // Members of the protocol at wrong places are not extensions (this has an invalid ID, so
+        // it is not a request, but it is not a notification either).
+        match parser.parse_str(r#"{"jsonrpc": "2.0", "method": "call", "id": [1]}"#) {
+            Err(Broken::Unmatched(_)) => (),
+            other => panic!("Misplaced member accepted: {:?}", other),
+        }
+    }
+
+    /// Parsing into the borrowed messages.
+    #[test]
+    fn borrowed() {
+        #[derive(Deserialize, Debug, PartialEq)]
+        struct Params<'a> {
+            name: &'a str,
+            count: u32,
+        }
+
+        let parser = Parser::new();
+        let input =
+            br#"{"jsonrpc": "2.0", "method": "call", "params": {"name": "x", "count": 2}, "id": 1}"#;
+        let request = match parser.parse_ref(input).unwrap() {
+            MessageRef::Request(request) => request,
+            other => panic!("Not a request: {:?}", other),
+        };
+        // The method is borrowed from the input
+        match request.method {
+            Cow::Borrowed("call") => (),
+            ref other => panic!("Unexpected method {:?}", other),
+        }
+        assert_eq!(Id::Number(1), request.id);
+        assert_eq!(
+            Params {
+                name: "x",
+                count: 2,
+            },
+            request.params().unwrap().unwrap()
+        );
+        // The owned copy is the same as parsing it directly
+        assert_eq!(
+            parser.parse_slice(input).unwrap(),
+            MessageRef::Request(request).to_message()
+        );
+        // An escaped method can't be borrowed, but it still works
+        match parser.parse_ref(br#"{"jsonrpc": "2.0", "method": "n\u00f6tif"}"#) {
+            Ok(MessageRef::Notification(ref notif)) => {
+                assert_eq!("nötif", notif.method);
+                assert!(notif.params::<Value>().is_none());
+            },
+            other => panic!("Not a notification: {:?}", other),
+        }
+        match parser.parse_ref(br#"{"jsonrpc": "2.0", "result": null, "id": "x"}"#) {
+            Ok(MessageRef::Response(ref response)) => {
+                assert_eq!("null", response.result.as_ref().unwrap().get());
+                assert_eq!(Id::from("x"), response.id);
+            },
+            other => panic!("Not a response: {:?}", other),
+        }
+        // The batches keep the broken sub-messages in place
+        let batch = br#"[{"jsonrpc": "2.0", "method": "notif"}, 42]"#;
+        match parser.parse_ref(batch) {
+            Ok(MessageRef::Batch(ref batch)) => {
+                assert_eq!(2, batch.len());
+                match batch[1] {
+                    MessageRef::UnmatchedSub(ref value) => assert_eq!(json!(42), *value),
+                    ref other => panic!("Not unmatched: {:?}", other),
+                }
+            },
+            other => panic!("Not a batch: {:?}", other),
+        }
+        // The same things as with the owned messages are refused
+        let refused: &[&[u8]] = &[
+            br#"{"method": "notif"}"#,
+            br#"{"jsonrpc": "2.0", "result": 42, "error": {"code": 42, "message": "!"}, "id": 1}"#,
+            br#"{"jsonrpc": "2.0", "result": 42}"#,
+            br#"{"jsonrpc": "2.0", "method": "weird", "params": 42, "others": 43, "id": 2}"#,
+            br#"{"jsonrpc": "2.0", "method": "weird", "result": 42, "id": 2}"#,
+            br#"{"x": [1, 2, 3]}"#,
+            b"42",
+        ];
+        for input in refused {
+            match parser.parse_ref(input) {
+                Err(Broken::Unmatched(_)) => (),
+                other => panic!("{:?} recognized: {:?}", input, other),
+            }
+        }
+        match parser.parse_ref(b"{]") {
+            Err(Broken::SyntaxError(_)) => (),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+        // Parsed in a single pass, but the same as the owned ones, including what is left unmatched
+        let same: &[&[u8]] = &[
+            br#"{"jsonrpc": "2.0", "method": "call", "params": [1, {"x": null}], "id": 3}"#,
+            br#"{"jsonrpc": "2.0", "method": "notif", "params": null}"#,
+            br#"{"jsonrpc": "2.0", "error": {"code": 42, "message": "!"}, "id": null}"#,
+            br#"[{"jsonrpc": "2.0", "method": "notif"}, [], {"jsonrpc": "2.0", "result": 1}]"#,
+            br#"[{"jsonrpc": "2.0", "method": "x", "method": "y"}, {"method": 42, "id": 1}]"#,
+            br#"[{"jsonrpc": "2.0", "method": "x", "params": 42, "id": {"a": [true]}}]"#,
+            br#"{"jsonrpc": "2.0", "method": "x", "id": 1.5}"#,
+            b"[]",
+            b"[1, 2",
+        ];
+        for input in same {
+            let owned = parser.parse_slice(input);
+            let borrowed = parser.parse_ref(input).map(|msg| msg.to_message());
+            assert_eq!(owned, borrowed, "{}", String::from_utf8_lossy(input));
+        }
+        // But the parser settings apply
+        let lenient = Parser::new().lenient(true).accept_v1(true);
+        match lenient.parse_ref(br#"{"method": "notif", "params": [], "id": null, "meta": 1}"#) {
+            Ok(MessageRef::Notification(ref notif)) => assert_eq!(Version::V1, notif.version()),
+            other => panic!("Not a notification: {:?}", other),
+        }
+    }
+
+    /// Classification of the error codes and the error constructors.
+    #[test]
+    fn error_codes() {
+        for &(code, kind) in &[
+            (-32_700, ErrorCode::ParseError),
+            (-32_600, ErrorCode::InvalidRequest),
+            (-32_601, ErrorCode::MethodNotFound),
+            (-32_602, ErrorCode::InvalidParams),
+            (-32_603, ErrorCode::InternalError),
+            (-32_000, ErrorCode::ServerError(-32_000)),
+            (-32_099, ErrorCode::ServerError(-32_099)),
+            (-32_100, ErrorCode::Reserved(-32_100)),
+            (-32_768, ErrorCode::Reserved(-32_768)),
+            (-32_769, ErrorCode::Application(-32_769)),
+            (-31_999, ErrorCode::Application(-31_999)),
+            (42, ErrorCode::Application(42)),
+        ] {
+            assert_eq!(kind, ErrorCode::from(code));
+            assert_eq!(code, i64::from(kind));
+        }
+        assert!(ErrorCode::InternalError.is_standard());
+        assert!(!ErrorCode::ServerError(-32_001).is_standard());
+        assert!(ErrorCode::ServerError(-32_001).is_server_error());
+        assert!(ErrorCode::Application(1).is_application());
+        assert!(!ErrorCode::Reserved(-32_100).is_application());
+
+        let internal = RpcError::internal_error(Some("oops"));
+        assert_eq!(ErrorCode::InternalError, internal.error_code());
+        assert_eq!("Internal error (-32603): \"oops\"", internal.to_string());
+        assert_eq!(
+            "Invalid request (-32600)",
+            RpcError::invalid_request().to_string()
+        );
+        let server = RpcError::server_error_with_code(-32_042, None::<()>).unwrap();
+        assert_eq!(ErrorCode::ServerError(-32_042), server.error_code());
+        assert_eq!(
+            InvalidServerErrorCode(-32_100),
+            RpcError::server_error_with_code(-32_100, None::<()>).unwrap_err()
+        );
+        assert_eq!(
+            InvalidServerErrorCode(1),
+            RpcError::server_error_with_code(1, None::<()>).unwrap_err()
+        );
+    }
+
     /// A helper for the `broken` test.
     ///
     /// Check that the given JSON string parses, but is not recognized as a valid RPC message.
@@ -513,21 +2277,285 @@ mod tests {
         };
     }
 
+    /// Test the parameters are only arrays or objects and the accessors to them.
+    #[test]
+    fn params() {
+        // Scalars are not valid parameters, both in the owned and borrowed messages
+        for params in &["42", "\"x\"", "true"] {
+            let input = format!(
+                r#"{{"jsonrpc": "2.0", "method": "call", "params": {}, "id": 1}}"#,
+                params
+            );
+            let broken = from_str(&input).unwrap_err();
+            assert_eq!(Some(Mismatch::InvalidMember("params".to_owned())), broken.mismatch());
+            assert_eq!(Some(Id::Number(1)), broken.id());
+            match Parser::new().parse_ref(input.as_bytes()) {
+                Err(Broken::Unmatched(_)) => (),
+                other => panic!("{} accepted: {:?}", input, other),
+            }
+        }
+        // Null is the same as no params
+        let input = r#"{"jsonrpc": "2.0", "method": "notif", "params": null}"#;
+        assert_eq!(Ok(Message::notification("notif".to_owned(), None)), from_str(input));
+        match Parser::new().parse_ref(input.as_bytes()) {
+            Ok(MessageRef::Notification(ref notif)) => assert!(notif.params.is_none()),
+            other => panic!("Not a notification: {:?}", other),
+        }
+
+        let positional = Params::try_from(json!([1, "x"])).unwrap();
+        assert_eq!(2, positional.len());
+        assert!(!positional.is_empty());
+        assert_eq!(Some(&json!("x")), positional.get_index(1));
+        assert_eq!(None, positional.get_index(2));
+        assert_eq!(None, positional.get_name("x"));
+        assert!(positional.as_named().is_none());
+        assert_eq!((1, "x".to_owned()), positional.parse().unwrap());
+        let named = Params::try_from(json!({"x": 42})).unwrap();
+        assert_eq!(Some(&json!(42)), named.get_name("x"));
+        assert_eq!(None, named.get_index(0));
+        assert!(named.as_positional().is_none());
+        assert_eq!(
+            ErrorCode::InvalidParams,
+            named.parse::<Vec<u32>>().unwrap_err().error_code()
+        );
+        assert_eq!(json!({"x": 42}), Value::from(named));
+        assert_eq!(Err(json!(42)), Params::try_from(json!(42)));
+        assert!(Params::from(Vec::new()).is_empty());
+    }
+
+    /// Test the details available about broken messages.
+    #[test]
+    fn broken_details() {
+        let unmatched = |input: &str| match from_str(input) {
+            Err(broken @ Broken::Unmatched(_)) => broken,
+            other => panic!("{} not unmatched: {:?}", input, other),
+        };
+
+        // The ID is recovered whenever it is valid and the message is a request
+        let broken = unmatched(r#"{"jsonrpc": "2.0", "method": "weird", "others": 43, "id": 2}"#);
+        assert_eq!(Some(Id::Number(2)), broken.id());
+        assert_eq!(Some(Mismatch::UnknownMember("others".to_owned())), broken.mismatch());
+        let reply = Message::Response(Response {
+            jsonrpc: Version::V2,
+            result: Err(RpcError::invalid_request()),
+            id: Id::Number(2),
+            extensions: Map::new(),
+        });
+        assert_eq!(reply, broken.reply());
+        let broken = unmatched(r#"{"jsonrpc": "3.0", "method": "call", "id": "x"}"#);
+        assert_eq!(Some(Id::from("x")), broken.id());
+        assert_eq!(Some(Mismatch::Version), broken.mismatch());
+        // But not when it's not valid
+        let broken = unmatched(r#"{"jsonrpc": "2.0", "method": "call", "id": [1]}"#);
+        assert_eq!(None, broken.id());
+        assert_eq!(Some(Mismatch::InvalidMember("id".to_owned())), broken.mismatch());
+        match broken.reply() {
+            Message::Response(ref response) => assert_eq!(Id::Null, response.id),
+            other => panic!("Not a response: {:?}", other),
+        }
+        // Nor when it's not a request, the ID belongs to the other side
+        for input in &[
+            r#"{"jsonrpc": "2.0", "id": 5, "resul": 1}"#,
+            r#"{"jsonrpc": "2.0", "method": "call", "result": 1, "id": 5}"#,
+            r#"{"jsonrpc": "2.0", "method": 42, "id": 5}"#,
+        ] {
+            let broken = unmatched(input);
+            assert_eq!(None, broken.id());
+            match broken.reply() {
+                Message::Response(ref response) => assert_eq!(Id::Null, response.id),
+                other => panic!("Not a response: {:?}", other),
+            }
+        }
+
+        // Other explanations
+        let mismatch = |input: &str| unmatched(input).mismatch().unwrap();
+        assert_eq!(Mismatch::Version, mismatch(r#"{"method": "notif"}"#));
+        assert_eq!(Mismatch::NotObject, mismatch("42"));
+        assert_eq!(
+            Mismatch::InvalidMember("method".to_owned()),
+            mismatch(r#"{"jsonrpc": "2.0", "method": 42}"#)
+        );
+        assert_eq!(
+            Mismatch::InvalidMember("error".to_owned()),
+            mismatch(r#"{"jsonrpc": "2.0", "error": 42, "id": 1}"#)
+        );
+        let both = r#"{"jsonrpc": "2.0", "result": 42, "error": {"code": 42, "message": "!"}, "id": 1}"#;
+        assert_eq!(Mismatch::Shape, mismatch(both));
+
+        // Syntax errors know where they happened
+        let broken = from_str("{\n]").unwrap_err();
+        match broken {
+            Broken::SyntaxError(ref e) => {
+                assert_eq!(2, e.line);
+                assert_eq!(1, e.column);
+                assert_eq!(e.message, format!("{}", e));
+            },
+            ref other => panic!("Not a syntax error: {:?}", other),
+        }
+        assert_eq!(None, broken.id());
+        assert_eq!(None, broken.mismatch());
+    }
+
+    /// Test the batch examples from the specification.
+    ///
+    /// Both the owned and the borrowed parsing is checked, they must agree.
+    #[test]
+    fn batches() {
+        let parser = Parser::new();
+        let invalid = json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": -32600,
+                "message": "Invalid request",
+            },
+            "id": null,
+        });
+        // A helper to check a batch rejected as a whole
+        let rejected = |parser: &Parser, input: &str| {
+            let broken = parser.parse_str(input).unwrap_err();
+            assert_eq!(Broken::Unmatched(::serde_json::from_str(input).unwrap()), broken);
+            assert_eq!(invalid, to_value(broken.reply()).unwrap());
+            match parser.parse_ref(input.as_bytes()) {
+                Err(Broken::Unmatched(_)) => (),
+                other => panic!("{} not rejected: {:?}", input, other),
+            }
+        };
+        // A helper to check each sub-message of a batch is unmatched
+        let unmatched = |parser: &Parser, input: &str| {
+            let values = match ::serde_json::from_str(input).unwrap() {
+                Value::Array(values) => values,
+                other => panic!("Not a batch: {:?}", other),
+            };
+            let expected = values.into_iter().map(Message::UnmatchedSub).collect();
+            assert_eq!(Ok(Message::Batch(expected)), parser.parse_str(input));
+            match parser.parse_ref(input.as_bytes()) {
+                Ok(MessageRef::Batch(ref batch)) => {
+                    assert!(batch
+                        .iter()
+                        .all(|sub| matches!(*sub, MessageRef::UnmatchedSub(_))))
+                },
+                other => panic!("{} not a batch: {:?}", input, other),
+            }
+        };
+
+        // An empty array gets a single error
+        rejected(&parser, "[]");
+        // A non-empty invalid batch is answered element by element
+        unmatched(&parser, "[1]");
+        unmatched(&parser, "[1, 2, 3]");
+        // Nested batches are not allowed, they are invalid requests inside the outer one
+        unmatched(&parser, "[[]]");
+        unmatched(&parser, r#"[[{"jsonrpc": "2.0", "method": "notify_hello", "params": [7]}]]"#);
+        // The rest of the batch is still fine
+        let mixed = r#"[
+            {"jsonrpc": "2.0", "method": "sum", "params": [1, 2, 4], "id": "1"},
+            {"jsonrpc": "2.0", "method": "notify_hello", "params": [7]},
+            {"jsonrpc": "2.0", "method": "subtract", "params": [42, 23], "id": "2"},
+            {"foo": "boo"},
+            [{"jsonrpc": "2.0", "method": "notify_sum", "params": [1, 2, 4]}]
+        ]"#;
+        match parser.parse_str(mixed) {
+            Ok(Message::Batch(ref batch)) => {
+                assert_eq!(5, batch.len());
+                match (&batch[0], &batch[1], &batch[2]) {
+                    (&Message::Request(_), &Message::Notification(_), &Message::Request(_)) => (),
+                    other => panic!("Wrong sub-messages: {:?}", other),
+                }
+                assert_eq!(Message::UnmatchedSub(json!({"foo": "boo"})), batch[3]);
+                let nested = json!([{
+                    "jsonrpc": "2.0",
+                    "method": "notify_sum",
+                    "params": [1, 2, 4],
+                }]);
+                assert_eq!(Message::UnmatchedSub(nested), batch[4]);
+            },
+            other => panic!("Not a batch: {:?}", other),
+        }
+        // A syntax error inside a batch is still a syntax error of the whole
+        match parser.parse_str(r#"[{"jsonrpc": "2.0", "method": "sum", "id": "1"},{"jsonrpc"]"#) {
+            Err(Broken::SyntaxError(_)) => (),
+            other => panic!("Something unexpected: {:?}", other),
+        }
+
+        // Limiting the length of the batches (the long ones are checked in the limits test)
+        let limited = Parser::new().max_batch(Some(2));
+        unmatched(&limited, "[1, 2]");
+        rejected(&limited, "[]");
+    }
+
+    /// Test the resource limits of the parser.
+    #[test]
+    fn limits() {
+        let exceeded = |parser: &Parser, input: &str, limit: Limit| {
+            let broken = parser.parse_str(input).unwrap_err();
+            assert_eq!(Broken::LimitExceeded(limit), broken);
+            assert_eq!(None, broken.id());
+            assert_eq!(None, broken.mismatch());
+            let reply = json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32600,
+                    "message": "Invalid request",
+                    "data": limit.to_string(),
+                },
+                "id": null,
+            });
+            assert_eq!(reply, to_value(broken.reply()).unwrap());
+            match parser.parse_ref(input.as_bytes()) {
+                Err(Broken::LimitExceeded(l)) => assert_eq!(limit, l),
+                other => panic!("{} not limited: {:?}", input, other),
+            }
+        };
+        let allowed = |parser: &Parser, input: &str| {
+            parser.parse_str(input).unwrap();
+            parser.parse_ref(input.as_bytes()).unwrap();
+        };
+        let request = r#"{"jsonrpc": "2.0", "method": "x", "params": [[1], {"a": []}], "id": 1}"#;
+        let batch = format!("[{0}, {0}, {0}]", request);
+
+        // No limits by default
+        allowed(&Parser::new(), request);
+        allowed(&Parser::new(), &batch);
+
+        let sized = Parser::new().max_size(Some(request.len()));
+        allowed(&sized, request);
+        exceeded(&sized, &batch, Limit::Size);
+        // The size is checked before anything else
+        exceeded(&sized, &"[".repeat(request.len() + 1), Limit::Size);
+
+        let deep = Parser::new().max_depth(Some(4));
+        allowed(&deep, request);
+        exceeded(&deep, &batch, Limit::Depth);
+        exceeded(&deep, r#"{"jsonrpc": "2.0", "method": "x", "params": [[[[]]]]}"#, Limit::Depth);
+        // Brackets inside strings don't count
+        allowed(&deep, r#"{"jsonrpc": "2.0", "method": "x", "params": ["[[[\"{{{"]}"#);
+        // Too deep even when it is not even valid JSON
+        exceeded(&deep, "[[[[[[[[", Limit::Depth);
+        assert!(!too_deep(b"]]]]", 1));
+
+        let short = Parser::new().max_batch(Some(2));
+        allowed(&short, request);
+        exceeded(&short, &batch, Limit::Batch);
+        exceeded(&short, "[1, 2, 3]", Limit::Batch);
+    }
+
     /// Test some non-trivial aspects of the constructors
     ///
     /// This doesn't have a full coverage, because there's not much to actually test there.
     /// Most of it is related to the ids.
     #[test]
     fn constructors() {
-        let msg1 = Message::request("call".to_owned(), Some(json!([1, 2, 3])));
-        let msg2 = Message::request("call".to_owned(), Some(json!([1, 2, 3])));
+        let msg1 = Message::request("call".to_owned(), Params::try_from(json!([1, 2, 3])).ok());
+        let msg2 = Message::request("call".to_owned(), Params::try_from(json!([1, 2, 3])).ok());
         // They differ, even when created with the same parameters
         assert_ne!(msg1, msg2);
         // And, specifically, they differ in the ID's
         let (req1, req2) = if let (Message::Request(req1), Message::Request(req2)) = (msg1, msg2) {
             assert_ne!(req1.id, req2.id);
-            assert!(req1.id.is_string());
-            assert!(req2.id.is_string());
+            match (&req1.id, &req2.id) {
+                (&Id::String(_), &Id::String(_)) => (),
+                other => panic!("Non-string IDs: {:?}", other),
+            }
             (req1, req2)
         } else {
             panic!("Non-request received");
@@ -538,9 +2566,10 @@ mod tests {
             assert_eq!(
                 *resp,
                 Response {
-                    jsonrpc: Version,
+                    jsonrpc: Version::V2,
                     result: Ok(json!([1, 2, 3])),
                     id: id1,
+                    extensions: Map::new(),
                 }
             );
         } else {
@@ -554,9 +2583,10 @@ mod tests {
             assert_eq!(
                 *resp,
                 Response {
-                    jsonrpc: Version,
+                    jsonrpc: Version::V2,
                     result: Err(RpcError::new(42, "Wrong!".to_owned(), None)),
                     id: id2,
+                    extensions: Map::new(),
                 }
             );
         } else {
@@ -569,9 +2599,10 @@ mod tests {
             assert_eq!(
                 *resp,
                 Response {
-                    jsonrpc: Version,
+                    jsonrpc: Version::V2,
                     result: Err(RpcError::new(43, "Also wrong!".to_owned(), None)),
-                    id: Value::Null,
+                    id: Id::Null,
+                    extensions: Map::new(),
                 }
             );
         } else {
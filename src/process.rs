@@ -0,0 +1,194 @@
+// Copyright 2017 tokio-jsonrpc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Talking to child processes over their stdio.
+//!
+//! Available with the `tokio-process` feature. The [`spawn`](fn.spawn.html) function starts a
+//! command with its standard input and output piped into a new
+//! [`Endpoint`](../endpoint/struct.Endpoint.html). It returns the endpoint's
+//! [`Client`](../endpoint/struct.Client.html) and a [`Child`](struct.Child.html) to manage the
+//! process.
+//!
+//! The lifetime of the process is bound to the endpoint. Once the endpoint ends (eg. by
+//! [`ServerCtl::terminate`](../endpoint/struct.ServerCtl.html#method.terminate) or
+//! [`kill`](../endpoint/struct.ServerCtl.html#method.kill), or by an error), the process is killed
+//! if it still runs and it is always reaped. If the process exits first, its output ends and so
+//! does the endpoint. If a graceful shutdown of the child is needed, ask it to exit (eg. by a
+//! notification) and wait for the [`Child`](struct.Child.html) before ending the endpoint.
+//!
+//! ```rust,no_run
+//! # extern crate futures;
+//! # extern crate tokio_core;
+//! # extern crate tokio_jsonrpc;
+//! # use std::process::Command;
+//! # use futures::Future;
+//! # use tokio_core::reactor::Core;
+//! # use tokio_jsonrpc::LineCodec;
+//! # use tokio_jsonrpc::process;
+//! # use tokio_jsonrpc::server::Empty;
+//! # fn main() {
+//! let mut core = Core::new().unwrap();
+//! let handle = core.handle();
+//! let (client, child) =
+//!     process::spawn(&mut Command::new("plugin"), LineCodec::new(), Empty, &handle).unwrap();
+//! let called = client.call("hello".to_owned(), None, None)
+//!     .and_then(|(_client, answered)| answered);
+//! let response = core.run(called).unwrap();
+//! // The client is dropped by now, so the endpoint ends and the child with it
+//! let status = core.run(child).unwrap();
+//! # }
+//! ```
+
+use std::io::{BufReader, Error as IoError};
+use std::process::{Command, ExitStatus, Stdio};
+
+use futures::{Async, Future, Poll, Stream};
+use futures::future::{self, Either};
+use futures::sync::mpsc::{unbounded, UnboundedSender};
+use futures::sync::oneshot::{channel as one_channel, Receiver as OneReceiver};
+use tokio_core::reactor::Handle;
+use tokio_io::codec::{Decoder, Encoder};
+use tokio_io::io::{lines, Lines};
+use tokio_process::{ChildStderr, ChildStdin, ChildStdout, CommandExt};
+
+use codec::Halves;
+use endpoint::{Client, Endpoint, Received};
+use message::Message;
+use server::Server;
+
+/// The connection to a child process.
+pub type Connection<Codec> = Halves<ChildStdout, ChildStdin, Codec>;
+
+/// The lines of the child's standard error output.
+pub type Stderr = Lines<BufReader<ChildStderr>>;
+
+/// A handle to a child process talking to an endpoint.
+///
+/// It is a future that resolves with the exit status once the process exits and is reaped.
+/// Dropping the handle doesn't end the process, it stays bound to the endpoint.
+#[derive(Debug)]
+pub struct Child {
+    id: u32,
+    killer: UnboundedSender<()>,
+    exited: OneReceiver<Result<ExitStatus, IoError>>,
+    stderr: Option<ChildStderr>,
+}
+
+impl Child {
+    /// The OS-assigned process identifier.
+    pub fn id(&self) -> u32 {
+        self.id
+    }
+    /// Kill the process.
+    ///
+    /// The process is killed inside the reactor, asynchronously. Then it is reaped and this
+    /// future resolves. The endpoint ends once it reads the rest of the output of the process.
+    pub fn kill(&self) {
+        // If the supervisor is gone, so is the process
+        drop(self.killer.unbounded_send(()));
+    }
+    /// Take the standard error output of the process, as a stream of lines.
+    ///
+    /// This is available only if the command had its stderr set to
+    /// [`Stdio::piped()`](https://doc.rust-lang.org/std/process/struct.Stdio.html#method.piped)
+    /// and only the first call returns it. Once taken, it should be read continuously, otherwise
+    /// the process blocks when the pipe fills up.
+    pub fn stderr(&mut self) -> Option<Stderr> {
+        self.stderr
+            .take()
+            .map(|stderr| lines(BufReader::new(stderr)))
+    }
+}
+
+impl Future for Child {
+    type Item = ExitStatus;
+    type Error = IoError;
+    fn poll(&mut self) -> Poll<ExitStatus, IoError> {
+        match self.exited.poll() {
+            Ok(Async::Ready(result)) => result.map(Async::Ready),
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Err(_) => Err(IoError::other("Lost the child process")),
+        }
+    }
+}
+
+/// Spawn the command and run an endpoint with the server over its stdio.
+///
+/// The standard input and output of the command are piped to the endpoint, framed by the codec.
+/// The endpoint runs on the handle. The standard error output is left as configured by the
+/// command.
+pub fn spawn<Codec, RpcServer>(
+    command: &mut Command, codec: Codec, server: RpcServer, handle: &Handle
+) -> Result<(Client, Child), IoError>
+where
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Clone + Send + 'static,
+    RpcServer: Server + 'static,
+{
+    spawn_with(command, codec, handle, |connection| {
+        Endpoint::new(connection, server)
+    })
+}
+
+/// Spawn the command and run a custom endpoint over its stdio.
+///
+/// This is like [`spawn`](fn.spawn.html), but the `build` closure creates the endpoint from the
+/// connection, so it can be configured (eg. by a logger).
+pub fn spawn_with<Codec, RpcServer, Build>(
+    command: &mut Command, codec: Codec, handle: &Handle, build: Build
+) -> Result<(Client, Child), IoError>
+where
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Clone + Send + 'static,
+    RpcServer: Server + 'static,
+    Build: FnOnce(Connection<Codec>) -> Endpoint<Connection<Codec>, RpcServer>,
+{
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn_async_with_handle(handle.new_tokio_handle())?;
+    let stdin = child.stdin().take().expect("The stdin is piped");
+    let stdout = child.stdout().take().expect("The stdout is piped");
+    let stderr = child.stderr().take();
+    let id = child.id();
+    let (client, finished) = build(Halves::new(stdout, stdin, codec)).start(handle);
+    let (killer, kill_requests) = unbounded();
+    // Only an explicit request kills, not dropping the Child handle
+    let kill_requested = kill_requests.into_future().then(|requested| match requested {
+        Ok((Some(()), _)) => Either::A(future::ok::<_, IoError>(())),
+        _ => Either::B(future::empty()),
+    });
+    // Errors of the endpoint end it as well, we don't care about them here
+    let ended = finished.then(|_| Ok(()));
+    let (exit_sender, exited) = one_channel();
+    let supervised = child
+        .select2(kill_requested.select(ended))
+        .then(|result| match result {
+            Ok(Either::A((status, _))) => Either::A(future::ok(status)),
+            Err(Either::A((e, _))) => Either::A(future::err(e)),
+            Ok(Either::B((_, mut child))) | Err(Either::B((_, mut child))) => {
+                // It may have exited in the meantime, but it still needs to be reaped
+                drop(child.kill());
+                Either::B(child)
+            },
+        })
+        .then(move |result| {
+            // Nobody may be waiting for the child
+            drop(exit_sender.send(result));
+            Ok(())
+        });
+    handle.spawn(supervised);
+    let child = Child {
+        id,
+        killer,
+        exited,
+        stderr,
+    };
+    Ok((client, child))
+}
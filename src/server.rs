@@ -16,7 +16,7 @@ use serde::Serialize;
 use serde_json::{to_value, Value};
 
 use endpoint::ServerCtl;
-use message::RpcError;
+use message::{NotificationRef, Params, RequestRef, RpcError};
 
 /// The server endpoint.
 ///
@@ -51,7 +51,7 @@ pub trait Server {
     /// However, the [`jsonrpc_params`](../macro.jsonrpc_params.html) macro may help in that
     /// regard.
     fn rpc(
-        &self, _ctl: &ServerCtl, _method: &str, _params: &Option<Value>
+        &self, _ctl: &ServerCtl, _method: &str, _params: &Option<Params>
     ) -> Option<Self::RpcCallResult> {
         None
     }
@@ -65,10 +65,33 @@ pub trait Server {
     /// However, the [`jsonrpc_params`](../macro.jsonrpc_params.html) macro may help in that
     /// regard.
     fn notification(
-        &self, _ctl: &ServerCtl, _method: &str, _params: &Option<Value>
+        &self, _ctl: &ServerCtl, _method: &str, _params: &Option<Params>
     ) -> Option<Self::NotificationResult> {
         None
     }
+    /// Called when the client requests something, with the request borrowed from the input.
+    ///
+    /// The endpoint calls this instead of [`rpc`](#method.rpc) when it parses the messages itself,
+    /// from the raw frames of the connection (see
+    /// [`Received`](../endpoint/enum.Received.html)). The parameters are still the raw JSON, so
+    /// they can be deserialized right into the types the method needs, without building the
+    /// `Value` first (with [`RequestRef::params`](../message/struct.RequestRef.html#method.params)
+    /// or the `raw` form of the [`jsonrpc_params`](../macro.jsonrpc_params.html) macro).
+    ///
+    /// The default implementation makes owned parameters and calls `rpc`.
+    fn rpc_ref(&self, ctl: &ServerCtl, request: &RequestRef) -> Option<Self::RpcCallResult> {
+        self.rpc(ctl, &request.method, &request.to_params())
+    }
+    /// Called when the client sends a notification, with the notification borrowed from the
+    /// input.
+    ///
+    /// This is the counterpart of [`rpc_ref`](#method.rpc_ref) for notifications. The default
+    /// implementation makes owned parameters and calls [`notification`](#method.notification).
+    fn notification_ref(
+        &self, ctl: &ServerCtl, notification: &NotificationRef
+    ) -> Option<Self::NotificationResult> {
+        self.notification(ctl, &notification.method, &notification.to_params())
+    }
     /// Called when the endpoint is initialized.
     ///
     /// It provides a default empty implementation, which can be overriden to hook onto the
@@ -118,25 +141,34 @@ pub type BoxRpcCallResult = Box<Future<Item = Value, Error = RpcError>>;
 /// A notification call result wrapping trait objects.
 pub type BoxNotificationResult = Box<Future<Item = (), Error = ()>>;
 
+/// Boxes the result of an RPC and converts its success into a `Value`.
+fn abstract_rpc<R>(result: R) -> BoxRpcCallResult
+where
+    R: IntoFuture<Error = RpcError> + 'static,
+    R::Item: Serialize,
+{
+    let future = result.into_future().map(|result| {
+        to_value(result).expect("Your result type is not convertible to JSON, which is a bug")
+    });
+    Box::new(future)
+}
+
 impl<S: Server> Server for AbstractServer<S> {
     type Success = Value;
     type RpcCallResult = BoxRpcCallResult;
     type NotificationResult = BoxNotificationResult;
     fn rpc(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::RpcCallResult> {
         self.0
             .rpc(ctl, method, params)
-            .map(|f| -> Box<Future<Item = Value, Error = RpcError>> {
-                let future = f.into_future().map(|result| {
-                    to_value(result)
-                        .expect("Your result type is not convertible to JSON, which is a bug")
-                });
-                Box::new(future)
-            })
+            .map(abstract_rpc)
+    }
+    fn rpc_ref(&self, ctl: &ServerCtl, request: &RequestRef) -> Option<Self::RpcCallResult> {
+        self.0.rpc_ref(ctl, request).map(abstract_rpc)
     }
     fn notification(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::NotificationResult> {
         // It seems the type signature is computed from inside the closure and it doesn't fit on
         // the outside, so we need to declare it manually :-(
@@ -144,6 +176,13 @@ impl<S: Server> Server for AbstractServer<S> {
             .notification(ctl, method, params)
             .map(|f| -> Box<Future<Item = (), Error = ()>> { Box::new(f.into_future()) })
     }
+    fn notification_ref(
+        &self, ctl: &ServerCtl, notification: &NotificationRef
+    ) -> Option<Self::NotificationResult> {
+        self.0
+            .notification_ref(ctl, notification)
+            .map(|f| -> Box<Future<Item = (), Error = ()>> { Box::new(f.into_future()) })
+    }
     fn initialized(&self, ctl: &ServerCtl) {
         self.0.initialized(ctl)
     }
@@ -200,15 +239,23 @@ impl Server for ServerChain {
     type RpcCallResult = BoxRpcCallResult;
     type NotificationResult = BoxNotificationResult;
     fn rpc(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::RpcCallResult> {
         self.iter_chain(|sub| sub.rpc(ctl, method, params))
     }
     fn notification(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::NotificationResult> {
         self.iter_chain(|sub| sub.notification(ctl, method, params))
     }
+    fn rpc_ref(&self, ctl: &ServerCtl, request: &RequestRef) -> Option<Self::RpcCallResult> {
+        self.iter_chain(|sub| sub.rpc_ref(ctl, request))
+    }
+    fn notification_ref(
+        &self, ctl: &ServerCtl, notification: &NotificationRef
+    ) -> Option<Self::NotificationResult> {
+        self.iter_chain(|sub| sub.notification_ref(ctl, notification))
+    }
     fn initialized(&self, ctl: &ServerCtl) {
         for sub in &self.0 {
             sub.initialized(ctl);
@@ -218,7 +265,7 @@ impl Server for ServerChain {
 
 /// Parses the parameters of an RPC or a notification.
 ///
-/// The [`Server`](server/trait.Server.html) receives `&Option<Value>` as the parameters when its
+/// The [`Server`](server/trait.Server.html) receives `&Option<Params>` as the parameters when its
 /// `notification` or `rpc` method is called and it needs to handle it. This means checking for
 /// validity and converting it to appropriate types. This is tedious.
 ///
@@ -240,20 +287,27 @@ impl Server for ServerChain {
 /// object is provided).
 ///
 /// If an empty parameter definition is provided, the macro checks that no parameters were sent
-/// (accepts no parameters sent, `null` sent as parameters and an empty object or array).
+/// (accepts no parameters sent and an empty object or array).
 ///
 /// If a single parameter is passed, in addition to trying positional and named parameters, the
-/// macro tries to convert the whole `Value` into the given type. This allows you to ask for
+/// macro tries to convert the whole parameters into the given type. This allows you to ask for
 /// a structure that holds all the named parameters.
 ///
-/// If you want to force the single parameter conversion of the whole `Value`, you can use the
-/// macro as `jsonrpc_params!(params, single Type)`. However, in this case it returns
+/// If you want to force the single conversion of a whole `Value`, you can use the macro as
+/// `jsonrpc_params!(value, single Type)`. However, in this case it returns
 /// `Result<Type, RpcError>` ‒ since it is expected you might want to try both named and
-/// positional decoding yourself. Also, it expects `&Value`, not `&Option<Value>`.
+/// positional decoding yourself. Also, it expects `&Value`, not `&Option<Params>`. The whole
+/// parameters can be converted by [`Params::parse`](message/enum.Params.html#method.parse).
 ///
 /// You can also force the macro to return the `Result<(Type, Type, ...), RpcError>` if you prefer,
 /// by prefixing the parameter definitions with the `wrap` token.
 ///
+/// The borrowed requests and notifications (see
+/// [`Server::rpc_ref`](server/trait.Server.html#method.rpc_ref)) keep the parameters as raw JSON.
+/// The `jsonrpc_params!(request.params, raw Type)` form decodes them as a whole right into the
+/// type, without building the `Value` first. The type may borrow from the input. Missing
+/// parameters decode as `null`, so an `Option` accepts them.
+///
 /// The macro has other variants than the mentioned here. They are mostly used internally by the
 /// macro itself and aren't meant to be used directly.
 ///
@@ -264,19 +318,25 @@ impl Server for ServerChain {
 /// ```rust
 /// # #[macro_use] extern crate tokio_jsonrpc;
 /// # #[macro_use] extern crate serde_json;
-/// # use tokio_jsonrpc::message::RpcError;
+/// # use std::convert::TryFrom;
+/// # use tokio_jsonrpc::message::{Params, RpcError};
 /// # use serde_json::Value;
-/// fn parse(params: &Option<Value>) -> Option<Result<(i32, bool), RpcError>> {
+/// fn parse(params: &Option<Params>) -> Option<Result<(i32, bool), RpcError>> {
 ///     Some(Ok(jsonrpc_params!(params, "num" => i32, "b" => bool)))
 /// }
 ///
+/// // Build the parameters from JSON
+/// fn params(value: Value) -> Option<Params> {
+///     Some(Params::try_from(value).unwrap())
+/// }
+///
 /// # fn main() {
-/// assert_eq!((42, true), parse(&Some(json!([42, true]))).unwrap().unwrap());
-/// assert_eq!((42, true), parse(&Some(json!({"num": 42, "b": true}))).unwrap().unwrap());
+/// assert_eq!((42, true), parse(&params(json!([42, true]))).unwrap().unwrap());
+/// assert_eq!((42, true), parse(&params(json!({"num": 42, "b": true}))).unwrap().unwrap());
 /// parse(&None).unwrap().unwrap_err();
-/// parse(&Some(json!({"num": "hello", "b": false}))).unwrap().unwrap_err();
+/// parse(&params(json!({"num": "hello", "b": false}))).unwrap().unwrap_err();
 /// // Return by the macro instead of exit
-/// assert_eq!((42, true), jsonrpc_params!(&Some(json!({"num": 42, "b": true})),
+/// assert_eq!((42, true), jsonrpc_params!(&params(json!({"num": 42, "b": true})),
 ///                                        wrap "num" => i32, "b" => bool).unwrap());
 /// jsonrpc_params!(&None, wrap "num" => i32, "b" => bool).unwrap_err();
 /// # }
@@ -288,19 +348,23 @@ impl Server for ServerChain {
 /// ```rust
 /// # #[macro_use] extern crate tokio_jsonrpc;
 /// # #[macro_use] extern crate serde_json;
-/// # use tokio_jsonrpc::message::RpcError;
+/// # use std::convert::TryFrom;
+/// # use tokio_jsonrpc::message::{Params, RpcError};
 /// # use serde_json::Value;
-/// fn parse(params: &Option<Value>) -> Option<Result<(Option<i32>, bool), RpcError>> {
+/// fn parse(params: &Option<Params>) -> Option<Result<(Option<i32>, bool), RpcError>> {
 ///     Some(Ok(jsonrpc_params!(params, named "num" => Option<i32>, "b" => bool)))
 /// }
+/// # fn params(value: Value) -> Option<Params> {
+/// #     Some(Params::try_from(value).unwrap())
+/// # }
 ///
 /// # fn main() {
-/// parse(&Some(json!([42, true]))).unwrap().unwrap_err();
-/// assert_eq!((Some(42), true), parse(&Some(json!({"num": 42, "b": true}))).unwrap().unwrap());
+/// parse(&params(json!([42, true]))).unwrap().unwrap_err();
+/// assert_eq!((Some(42), true), parse(&params(json!({"num": 42, "b": true}))).unwrap().unwrap());
 /// assert_eq!((None, false),
-///            parse(&Some(json!({"b": false, "extra": "ignored"}))).unwrap().unwrap());
+///            parse(&params(json!({"b": false, "extra": "ignored"}))).unwrap().unwrap());
 /// parse(&None).unwrap().unwrap_err();
-/// parse(&Some(json!({"num": "hello", "b": false}))).unwrap().unwrap_err();
+/// parse(&params(json!({"num": "hello", "b": false}))).unwrap().unwrap_err();
 /// # }
 /// ```
 ///
@@ -309,14 +373,14 @@ impl Server for ServerChain {
 /// ```rust
 /// # #[macro_use] extern crate tokio_jsonrpc;
 /// # #[macro_use] extern crate serde_json;
-/// # use tokio_jsonrpc::message::RpcError;
-/// # use serde_json::Value;
-/// fn parse(params: &Option<Value>) -> Option<Result<(i32, bool), RpcError>> {
+/// # use tokio_jsonrpc::message::{Params, RpcError};
+/// fn parse(params: &Option<Params>) -> Option<Result<(i32, bool), RpcError>> {
 ///     Some(Ok(jsonrpc_params!(params, positional i32, bool)))
 /// }
 ///
 /// # fn main() {
-/// assert_eq!((42, true), parse(&Some(json!([42, true]))).unwrap().unwrap());
+/// let params = Some(Params::Positional(vec![json!(42), json!(true)]));
+/// assert_eq!((42, true), parse(&params).unwrap().unwrap());
 /// # }
 /// ```
 ///
@@ -326,42 +390,79 @@ impl Server for ServerChain {
 /// # #[macro_use] extern crate tokio_jsonrpc;
 /// # #[macro_use] extern crate serde_json;
 /// # #[macro_use] extern crate serde_derive;
-/// # use tokio_jsonrpc::message::RpcError;
+/// # use std::convert::TryFrom;
+/// # use tokio_jsonrpc::message::{Params, RpcError};
 /// # use serde_json::Value;
 ///
 /// #[derive(PartialEq, Debug, Deserialize)]
-/// struct Params {
+/// struct Args {
 ///     num: Option<i32>,
 ///     b: bool,
 /// }
 ///
-/// fn parse(params: &Option<Value>) -> Option<Result<Params, RpcError>> {
-///     let (params,) = jsonrpc_params!(params, "params" => Params);
-///     Some(Ok(params))
+/// fn parse(params: &Option<Params>) -> Option<Result<Args, RpcError>> {
+///     let (args,) = jsonrpc_params!(params, "args" => Args);
+///     Some(Ok(args))
 /// }
+/// # fn params(value: Value) -> Option<Params> {
+/// #     Some(Params::try_from(value).unwrap())
+/// # }
 ///
 /// # fn main() {
-/// let expected = Params {
+/// let expected = Args {
 ///     num: Some(42),
 ///     b: true,
 /// };
-/// let expected_optional = Params {
+/// let expected_optional = Args {
 ///     num: None,
 ///     b: false,
 /// };
 ///
-/// assert_eq!(expected, parse(&Some(json!([42, true]))).unwrap().unwrap());
-/// assert_eq!(expected, parse(&Some(json!({"num": 42, "b": true}))).unwrap().unwrap());
-/// assert_eq!(expected_optional, parse(&Some(json!({"b": false}))).unwrap().unwrap());
+/// assert_eq!(expected, parse(&params(json!([42, true]))).unwrap().unwrap());
+/// assert_eq!(expected, parse(&params(json!({"num": 42, "b": true}))).unwrap().unwrap());
+/// assert_eq!(expected_optional, parse(&params(json!({"b": false}))).unwrap().unwrap());
 /// // This is accepted mostly as a side effect.
-/// assert_eq!(expected, parse(&Some(json!([{"num": 42, "b": true}]))).unwrap().unwrap());
+/// assert_eq!(expected, parse(&params(json!([{"num": 42, "b": true}]))).unwrap().unwrap());
 /// // As is this.
-/// assert_eq!(expected, parse(&Some(json!({"params": {"num": 42, "b": true}}))).unwrap().unwrap());
+/// assert_eq!(expected, parse(&params(json!({"args": {"num": 42, "b": true}}))).unwrap().unwrap());
 /// // If you mind the above limitations, you can ask directly for a single value decoding.
 /// // That returs a Result directly.
 /// assert_eq!(expected,
-///            jsonrpc_params!(&json!({"num": 42, "b": true}), single Params).unwrap());
-/// jsonrpc_params!(&json!([{"num": 42, "b": true}]), single Params).unwrap_err();
+///            jsonrpc_params!(&json!({"num": 42, "b": true}), single Args).unwrap());
+/// jsonrpc_params!(&json!([{"num": 42, "b": true}]), single Args).unwrap_err();
+/// # }
+/// ```
+///
+/// Decoding the raw parameters of a borrowed request:
+///
+/// ```rust
+/// # #[macro_use] extern crate tokio_jsonrpc;
+/// # #[macro_use] extern crate serde_derive;
+/// # use tokio_jsonrpc::message::{MessageRef, Parser, RequestRef, RpcError};
+///
+/// #[derive(Deserialize)]
+/// struct Args<'a> {
+///     name: &'a str,
+///     count: Option<u32>,
+/// }
+///
+/// fn parse(request: &RequestRef) -> Option<Result<(String, u32), RpcError>> {
+///     let args = jsonrpc_params!(request.params, raw Args);
+///     Some(Ok((args.name.to_owned(), args.count.unwrap_or(1))))
+/// }
+///
+/// # fn main() {
+/// let parse_json = |input: &str| {
+///     let parser = Parser::new();
+///     match parser.parse_ref(input.as_bytes()).unwrap() {
+///         MessageRef::Request(request) => parse(&request).unwrap(),
+///         _ => unreachable!(),
+///     }
+/// };
+/// let greet = r#"{"jsonrpc": "2.0", "method": "greet", "params": {"name": "x"}, "id": 1}"#;
+/// assert_eq!(("x".to_owned(), 1), parse_json(greet).unwrap());
+/// let missing = r#"{"jsonrpc": "2.0", "method": "greet", "id": 1}"#;
+/// parse_json(missing).unwrap_err();
 /// # }
 /// ```
 #[macro_export]
@@ -372,10 +473,8 @@ macro_rules! jsonrpc_params {
     ( $value:expr, ) => {
         match *$value {
             // Accept the empty values
-            None |
-            Some($crate::macro_exports::Value::Null) => (),
-            Some($crate::macro_exports::Value::Array(ref arr)) if arr.len() == 0 => (),
-            Some($crate::macro_exports::Value::Object(ref obj)) if obj.len() == 0 => (),
+            None => (),
+            Some(ref params) if params.is_empty() => (),
             // If it's anything else, complain
             _ => {
                 return Some(Err($crate::message::RpcError::
@@ -394,6 +493,19 @@ macro_rules! jsonrpc_params {
             $crate::message::RpcError::invalid_params(Some(format!("Incompatible type: {}", e)))
         })
     }};
+    // Decode the raw parameters of a borrowed message as a whole, right into the type
+    ( $value:expr, raw $vartype:ty ) => {{
+        let val: $crate::macro_exports::Option<&$crate::macro_exports::RawValue> = $value;
+        // The missing parameters are the same as null ones
+        let json = val.map_or("null", |raw| raw.get());
+        match $crate::macro_exports::from_str::<$vartype>(json) {
+            Ok(result) => result,
+            Err(e) => {
+                let err = format!("Incompatible type: {}", e);
+                return Some(Err($crate::message::RpcError::invalid_params(Some(err))).into());
+            },
+        }
+    }};
     // A helper to count number of arguments
     ( arity $head:ty ) => { 1 };
     ( arity $head:ty, $( $tail:ty ),* ) => { 1 + jsonrpc_params!(arity $( $tail ),*) };
@@ -425,11 +537,11 @@ macro_rules! jsonrpc_params {
     // Possibly multiple arguments, enforcing positional coding (in an array)
     // It uses recursion to count and access the items in the vector
     ( $value:expr, positional $( $vartype:ty ),+ ) => {{
-        let val: &$crate::macro_exports::Option<$crate::macro_exports::Value> = $value;
+        let val: &$crate::macro_exports::Option<$crate::message::Params> = $value;
         match *val {
             None => return Some(Err($crate::message::RpcError::
                                     invalid_params(Some("Expected parameters".to_owned()))).into()),
-            Some($crate::macro_exports::Value::Array(ref vec)) => {
+            Some($crate::message::Params::Positional(ref vec)) => {
                 let cnt = jsonrpc_params!(arity $( $vartype ),+);
                 if cnt != vec.len() {
                     let err = format!("Wrong number of parameters: expected: {}, got: {}", cnt,
@@ -450,11 +562,11 @@ macro_rules! jsonrpc_params {
     // It can handle optional arguments in a way, but it has its limits (eg. a non-optional string
     // defaults to an empty one if it is missing).
     ( $value:expr, named $( $varname:expr => $vartype:ty ),+ ) => {{
-        let val: &$crate::macro_exports::Option<$crate::macro_exports::Value> = $value;
+        let val: &$crate::macro_exports::Option<$crate::message::Params> = $value;
         match *val {
             None => return Some(Err($crate::message::RpcError::
                                     invalid_params(Some("Expected parameters".to_owned()))).into()),
-            Some($crate::macro_exports::Value::Object(ref map)) => {
+            Some($crate::message::Params::Named(ref map)) => {
                 (
                     $(
                         {
@@ -479,30 +591,25 @@ macro_rules! jsonrpc_params {
     }};
     // Decode params, decide if named or positional based on what arrived
     ( $value:expr, decide $( $varname:expr => $vartype:ty ),+ ) => {{
-        let val: &$crate::macro_exports::Option<$crate::macro_exports::Value> = $value;
+        let val: &$crate::macro_exports::Option<$crate::message::Params> = $value;
         match *val {
             None => return Some(Err($crate::message::RpcError::
                                     invalid_params(Some("Expected parameters".to_owned()))).into()),
-            Some($crate::macro_exports::Value::Array(_)) => {
+            Some($crate::message::Params::Positional(_)) => {
                 jsonrpc_params!(val, positional $( $vartype ),+)
             },
-            Some($crate::macro_exports::Value::Object(_)) => {
+            Some($crate::message::Params::Named(_)) => {
                 jsonrpc_params!(val, named $( $varname => $vartype ),+)
             },
-            Some(_) => {
-                return Some(Err($crate::message::RpcError::
-                                invalid_params(Some("Expected an object or an array as parameters"
-                                                    .to_owned()))).into());
-            },
         }
     }};
     // A special case for a single param.
     //
     // We allow decoding it directly, mostly to support users with a complex all-params structure.
     ( $value:expr, $varname:expr => $vartype:ty ) => {{
-        let val: &$crate::macro_exports::Option<$crate::macro_exports::Value> = $value;
+        let val: &$crate::macro_exports::Option<$crate::message::Params> = $value;
         // First try decoding directly
-        let single = val.as_ref().map(|val| jsonrpc_params!(val, single $vartype));
+        let single = val.as_ref().map(|val| val.parse::<$vartype>());
         if let Some(Ok(result)) = single {
             (result,)
         } else {
@@ -517,7 +624,7 @@ macro_rules! jsonrpc_params {
     // Return multiple values as a result
     ( $value:expr, wrap $( $varname:expr => $vartype:ty ),+ ) => {
         {
-            fn convert(params: &$crate::macro_exports::Option<$crate::macro_exports::Value>)
+            fn convert(params: &$crate::macro_exports::Option<$crate::message::Params>)
                        -> $crate::macro_exports::Option<
                            $crate::macro_exports::Result<($( $vartype, )+),
                                                          $crate::message::RpcError>> {
@@ -528,7 +635,7 @@ macro_rules! jsonrpc_params {
     };
     ( $value:expr, wrap named $( $varname:expr => $vartype:ty ),+ ) => {
         {
-            fn convert(params: &$crate::macro_exports::Option<$crate::macro_exports::Value>)
+            fn convert(params: &$crate::macro_exports::Option<$crate::message::Params>)
                        -> $crate::macro_exports::Option<
                            $crate::macro_exports::Result<($( $vartype, )+),
                                                           $crate::message::RpcError>> {
@@ -539,7 +646,7 @@ macro_rules! jsonrpc_params {
     };
     ( $value:expr, wrap positional $( $vartype:ty ),+ ) => {
         {
-            fn convert(params: &$crate::macro_exports::Option<$crate::macro_exports::Value>)
+            fn convert(params: &$crate::macro_exports::Option<$crate::message::Params>)
                        -> $crate::macro_exports::Option<
                            $crate::macro_exports::Result<($( $vartype, )+),
                                                          $crate::message::RpcError>> {
@@ -624,10 +731,16 @@ macro_rules! json_rpc_impl {
 #[cfg(test)]
 mod tests {
     use std::cell::{Cell, RefCell};
+    use std::convert::TryFrom;
     use serde_json::Map;
 
     use super::*;
 
+    /// Build the parameters from JSON.
+    fn params(value: Value) -> Option<Params> {
+        Some(Params::try_from(value).unwrap())
+    }
+
     /// Check the empty server is somewhat sane.
     #[test]
     fn empty() {
@@ -665,7 +778,7 @@ mod tests {
         type RpcCallResult = Result<bool, RpcError>;
         type NotificationResult = Result<(), ()>;
         fn rpc(
-            &self, _ctl: &ServerCtl, method: &str, params: &Option<Value>
+            &self, _ctl: &ServerCtl, method: &str, params: &Option<Params>
         ) -> Option<Self::RpcCallResult> {
             self.update(&self.rpc);
             match method {
@@ -677,7 +790,7 @@ mod tests {
             }
         }
         fn notification(
-            &self, _ctl: &ServerCtl, method: &str, params: &Option<Value>
+            &self, _ctl: &ServerCtl, method: &str, params: &Option<Params>
         ) -> Option<Self::NotificationResult> {
             self.update(&self.notification);
             assert!(params.is_none());
@@ -735,9 +848,9 @@ mod tests {
         type RpcCallResult = Result<usize, RpcError>;
         type NotificationResult = Result<(), ()>;
         fn rpc(
-            &self, _ctl: &ServerCtl, method: &str, params: &Option<Value>
+            &self, _ctl: &ServerCtl, method: &str, params: &Option<Params>
         ) -> Option<Self::RpcCallResult> {
-            assert!(params.as_ref().unwrap().is_null());
+            assert!(params.as_ref().unwrap().is_empty());
             match method {
                 "another" => Some(Ok(42)),
                 _ => None,
@@ -769,12 +882,12 @@ mod tests {
         assert_eq!(
             json!(42),
             chain
-                .rpc(&ctl, "another", &Some(Value::Null))
+                .rpc(&ctl, "another", &params(json!([])))
                 .unwrap()
                 .wait()
                 .unwrap()
         );
-        assert!(chain.rpc(&ctl, "wrong", &Some(Value::Null)).is_none());
+        assert!(chain.rpc(&ctl, "wrong", &params(json!([]))).is_none());
         chain
             .notification(&ctl, "notification", &None)
             .unwrap()
@@ -825,7 +938,7 @@ mod tests {
     ///
     /// It is a separate function so the return error thing from the macro doesn't end the test
     /// prematurely (actually, it wouldn't, as the return type doesn't match).
-    fn expect_no_params(params: &Option<Value>) -> Option<Result<(), RpcError>> {
+    fn expect_no_params(params: &Option<Params>) -> Option<Result<(), RpcError>> {
         // Check that we can actually assign it somewhere (this may be needed in other macros later
         // on.
         let () = jsonrpc_params!(params,);
@@ -837,25 +950,17 @@ mod tests {
     fn params_macro_none() {
         // These are legal no-params, at least for us
         expect_no_params(&None).unwrap().unwrap();
-        expect_no_params(&Some(Value::Null)).unwrap().unwrap();
-        expect_no_params(&Some(Value::Array(Vec::new())))
+        expect_no_params(&Some(Params::Positional(Vec::new())))
             .unwrap()
             .unwrap();
-        expect_no_params(&Some(Value::Object(Map::new())))
+        expect_no_params(&Some(Params::Named(Map::new())))
             .unwrap()
             .unwrap();
         // Some illegal values
-        expect_no_params(&Some(Value::Bool(true)))
-            .unwrap()
-            .unwrap_err();
-        expect_no_params(&Some(json!([42, "hello"])))
+        expect_no_params(&params(json!([42, "hello"])))
             .unwrap()
             .unwrap_err();
-        expect_no_params(&Some(json!({"hello": 42})))
-            .unwrap()
-            .unwrap_err();
-        expect_no_params(&Some(json!(42))).unwrap().unwrap_err();
-        expect_no_params(&Some(json!("hello")))
+        expect_no_params(&params(json!({"hello": 42})))
             .unwrap()
             .unwrap_err();
     }
@@ -877,7 +982,7 @@ mod tests {
     /// A helper function to decode two values as positional arguments.
     ///
     /// This is to prevent attempt to return errors from within the test function.
-    fn bool_str_positional(value: &Option<Value>) -> Option<Result<(bool, String), RpcError>> {
+    fn bool_str_positional(value: &Option<Params>) -> Option<Result<(bool, String), RpcError>> {
         let (b, s) = jsonrpc_params!(value, positional bool, String);
         Some(Ok((b, s)))
     }
@@ -889,7 +994,7 @@ mod tests {
     /// sometimes the ends of recursion), we mostly want to check it compiles.
     ///
     /// It also checks we don't get confused with an array inside the parameter array.
-    fn single_positional(value: &Option<Value>) -> Option<Result<Vec<String>, RpcError>> {
+    fn single_positional(value: &Option<Params>) -> Option<Result<Vec<String>, RpcError>> {
         let (r,) = jsonrpc_params!(value, positional Vec<String>);
         Some(Ok(r))
     }
@@ -899,46 +1004,40 @@ mod tests {
     fn positional() {
         // Some that don't match
         bool_str_positional(&None).unwrap().unwrap_err();
-        bool_str_positional(&Some(Value::Null))
-            .unwrap()
-            .unwrap_err();
-        bool_str_positional(&Some(Value::Bool(true)))
+        bool_str_positional(&params(json!({"b": true, "s": "hello"})))
             .unwrap()
             .unwrap_err();
-        bool_str_positional(&Some(json!({"b": true, "s": "hello"})))
+        bool_str_positional(&params(json!([true])))
             .unwrap()
             .unwrap_err();
-        bool_str_positional(&Some(json!([true])))
+        bool_str_positional(&params(json!([true, "hello", false])))
             .unwrap()
             .unwrap_err();
-        bool_str_positional(&Some(json!([true, "hello", false])))
-            .unwrap()
-            .unwrap_err();
-        bool_str_positional(&Some(json!([true, true])))
+        bool_str_positional(&params(json!([true, true])))
             .unwrap()
             .unwrap_err();
         // This one should be fine
         assert_eq!(
             (true, "hello".to_owned()),
-            bool_str_positional(&Some(json!([true, "hello"])))
+            bool_str_positional(&params(json!([true, "hello"])))
                 .unwrap()
                 .unwrap()
         );
 
         single_positional(&None).unwrap().unwrap_err();
         // We need two nested arrays
-        single_positional(&Some(json!(["Hello"])))
+        single_positional(&params(json!(["Hello"])))
             .unwrap()
             .unwrap_err();
         assert!(
-            single_positional(&Some(json!([[]])))
+            single_positional(&params(json!([[]])))
                 .unwrap()
                 .unwrap()
                 .is_empty()
         );
         assert_eq!(
             vec!["hello", "world"],
-            single_positional(&Some(json!([["hello", "world"]])))
+            single_positional(&params(json!([["hello", "world"]])))
                 .unwrap()
                 .unwrap()
         );
@@ -949,13 +1048,11 @@ mod tests {
     fn positional_direct() {
         let mut guard = PanicGuard::new();
         jsonrpc_params!(&None, wrap positional bool, String).unwrap_err();
-        jsonrpc_params!(&Some(Value::Null), wrap positional bool, String).unwrap_err();
-        jsonrpc_params!(&Some(Value::Bool(true)), wrap positional bool, String).unwrap_err();
-        jsonrpc_params!(&Some(json!({"b": true, "s": "hello"})), wrap positional bool, String)
+        jsonrpc_params!(&params(json!({"b": true, "s": "hello"})), wrap positional bool, String)
             .unwrap_err();
         assert_eq!(
             (true, "hello".to_owned()),
-            jsonrpc_params!(&Some(json!([true, "hello"])),
+            jsonrpc_params!(&params(json!([true, "hello"])),
                                    wrap positional bool, String)
                 .unwrap()
         );
@@ -963,7 +1060,7 @@ mod tests {
     }
 
     /// Helper function to decode two values as named arguments
-    fn bool_str_named(value: &Option<Value>) -> Option<Result<(bool, String), RpcError>> {
+    fn bool_str_named(value: &Option<Params>) -> Option<Result<(bool, String), RpcError>> {
         let (b, s) = jsonrpc_params!(value, named "b" => bool, "s" => String);
         Some(Ok((b, s)))
     }
@@ -974,13 +1071,13 @@ mod tests {
     }
 
     /// Like above, but with only one parameter.
-    fn single_named(value: &Option<Value>) -> Option<Result<TestStruct, RpcError>> {
+    fn single_named(value: &Option<Params>) -> Option<Result<TestStruct, RpcError>> {
         let (ts,) = jsonrpc_params!(value, named "ts" => TestStruct);
         Some(Ok(ts))
     }
 
     /// Test an optional value might be missing.
-    fn optional_named(value: &Option<Value>) -> Option<Result<Option<u32>, RpcError>> {
+    fn optional_named(value: &Option<Params>) -> Option<Result<Option<u32>, RpcError>> {
         let (ov,) = jsonrpc_params!(value, named "ov" => Option<u32>);
         Some(Ok(ov))
     }
@@ -989,48 +1086,44 @@ mod tests {
     #[test]
     fn named() {
         bool_str_named(&None).unwrap().unwrap_err();
-        bool_str_named(&Some(Value::Null)).unwrap().unwrap_err();
-        bool_str_named(&Some(Value::Bool(true)))
-            .unwrap()
-            .unwrap_err();
-        bool_str_named(&Some(json!([true, "hello"])))
+        bool_str_named(&params(json!([true, "hello"])))
             .unwrap()
             .unwrap_err();
-        bool_str_named(&Some(json!({"b": true, "s": 42})))
+        bool_str_named(&params(json!({"b": true, "s": 42})))
             .unwrap()
             .unwrap_err();
         // FIXME: This fails, as serde_json considers Value::Null to be an empty string
-        //bool_str_named(&Some(json!({"b": true}))).unwrap_err();
-        bool_str_named(&Some(json!({"s": "hello"})))
+        //bool_str_named(&params(json!({"b": true}))).unwrap_err();
+        bool_str_named(&params(json!({"s": "hello"})))
             .unwrap()
             .unwrap_err();
         assert_eq!(
             (true, "hello".to_owned()),
-            bool_str_named(&Some(json!({"b": true, "s": "hello"})))
+            bool_str_named(&params(json!({"b": true, "s": "hello"})))
                 .unwrap()
                 .unwrap()
         );
         // FIXME: We currently don't know how to check against extra params
         assert_eq!(
             (true, "hello".to_owned()),
-            bool_str_named(&Some(json!({"b": true, "s": "hello", "x": 42})))
+            bool_str_named(&params(json!({"b": true, "s": "hello", "x": 42})))
                 .unwrap()
                 .unwrap()
         );
 
         single_named(&None).unwrap().unwrap_err();
-        single_named(&Some(json!({"ts": 42}))).unwrap().unwrap_err();
-        single_named(&Some(json!({"ts": {"x": 42}})))
+        single_named(&params(json!({"ts": 42}))).unwrap().unwrap_err();
+        single_named(&params(json!({"ts": {"x": 42}})))
             .unwrap()
             .unwrap();
 
         optional_named(&None).unwrap().unwrap_err();
-        optional_named(&Some(json!([]))).unwrap().unwrap_err();
+        optional_named(&params(json!([]))).unwrap().unwrap_err();
         assert_eq!(
             Some(42),
-            optional_named(&Some(json!({"ov": 42}))).unwrap().unwrap()
+            optional_named(&params(json!({"ov": 42}))).unwrap().unwrap()
         );
-        assert_eq!(None, optional_named(&Some(json!({}))).unwrap().unwrap());
+        assert_eq!(None, optional_named(&params(json!({}))).unwrap().unwrap());
     }
 
     /// Like `named`, but using auto-wrapping support from the macro.
@@ -1038,17 +1131,13 @@ mod tests {
     fn named_direct() {
         let mut guard = PanicGuard::new();
         jsonrpc_params!(&None, wrap named "b" => bool, "s" => String).unwrap_err();
-        jsonrpc_params!(&Some(Value::Null), wrap named "b" => bool, "s" => String).unwrap_err();
-        jsonrpc_params!(&Some(Value::Bool(true)),
-                        wrap named "b" => bool, "s" => String)
-            .unwrap_err();
         assert_eq!(
             (true, "hello".to_owned()),
-            jsonrpc_params!(&Some(json!({"b": true, "s": "hello"})),
+            jsonrpc_params!(&params(json!({"b": true, "s": "hello"})),
                                    wrap "b" => bool, "s" => String)
                 .unwrap()
         );
-        jsonrpc_params!(&Some(json!([true, "hello"])), wrap named "b" => bool, "s" => String)
+        jsonrpc_params!(&params(json!([true, "hello"])), wrap named "b" => bool, "s" => String)
             .unwrap_err();
         guard.disarm();
     }
@@ -1056,7 +1145,7 @@ mod tests {
     /// A helper function to decode two parameters.
     ///
     /// The decoding decides how to do so based on what arrived.
-    fn bool_str(value: &Option<Value>) -> Option<Result<(bool, String), RpcError>> {
+    fn bool_str(value: &Option<Params>) -> Option<Result<(bool, String), RpcError>> {
         let (b, s) = jsonrpc_params!(value, "b" => bool, "s" => String);
         Some(Ok((b, s)))
     }
@@ -1065,17 +1154,15 @@ mod tests {
     #[test]
     fn decide() {
         bool_str(&None).unwrap().unwrap_err();
-        bool_str(&Some(Value::Null)).unwrap().unwrap_err();
-        bool_str(&Some(Value::Bool(true))).unwrap().unwrap_err();
         assert_eq!(
             (true, "hello".to_owned()),
-            bool_str_named(&Some(json!({"b": true, "s": "hello"})))
+            bool_str_named(&params(json!({"b": true, "s": "hello"})))
                 .unwrap()
                 .unwrap()
         );
         assert_eq!(
             (true, "hello".to_owned()),
-            bool_str_positional(&Some(json!([true, "hello"])))
+            bool_str_positional(&params(json!([true, "hello"])))
                 .unwrap()
                 .unwrap()
         );
@@ -1086,17 +1173,15 @@ mod tests {
     fn decide_direct() {
         let mut guard = PanicGuard::new();
         jsonrpc_params!(&None, wrap "b" => bool, "s" => String).unwrap_err();
-        jsonrpc_params!(&Some(Value::Null), wrap "b" => bool, "s" => String).unwrap_err();
-        jsonrpc_params!(&Some(Value::Bool(true)), wrap "b" => bool, "s" => String).unwrap_err();
         assert_eq!(
             (true, "hello".to_owned()),
-            jsonrpc_params!(&Some(json!({"b": true, "s": "hello"})),
+            jsonrpc_params!(&params(json!({"b": true, "s": "hello"})),
                                    wrap "b" => bool, "s" => String)
                 .unwrap()
         );
         assert_eq!(
             (true, "hello".to_owned()),
-            jsonrpc_params!(&Some(json!([true, "hello"])),
+            jsonrpc_params!(&params(json!([true, "hello"])),
                                    wrap "b" => bool, "s" => String)
                 .unwrap()
         );
@@ -1104,7 +1189,7 @@ mod tests {
     }
 
     /// A helper for the `decide_single` test.
-    fn decode_test_struct(value: &Option<Value>) -> Option<Result<TestStruct, RpcError>> {
+    fn decode_test_struct(value: &Option<Params>) -> Option<Result<TestStruct, RpcError>> {
         let (ts,) = jsonrpc_params!(value, "ts" => TestStruct);
         Some(Ok(ts))
     }
@@ -1117,29 +1202,25 @@ mod tests {
     #[test]
     fn decide_single() {
         decode_test_struct(&None).unwrap().unwrap_err();
-        decode_test_struct(&Some(Value::Null)).unwrap().unwrap_err();
-        decode_test_struct(&Some(Value::Bool(true)))
-            .unwrap()
-            .unwrap_err();
 
         // Encoded as an array
         assert_eq!(
             TestStruct { x: 42 },
-            decode_test_struct(&Some(json!([{"x": 42}])))
+            decode_test_struct(&params(json!([{"x": 42}])))
                 .unwrap()
                 .unwrap()
         );
         // Encoded as an object
         assert_eq!(
             TestStruct { x: 42 },
-            decode_test_struct(&Some(json!({"ts": {"x": 42}})))
+            decode_test_struct(&params(json!({"ts": {"x": 42}})))
                 .unwrap()
                 .unwrap()
         );
         // Encoded directly as the parameters structure
         assert_eq!(
             TestStruct { x: 42 },
-            decode_test_struct(&Some(json!({"x": 42})))
+            decode_test_struct(&params(json!({"x": 42})))
                 .unwrap()
                 .unwrap()
         );
@@ -0,0 +1,231 @@
+// Copyright 2017 tokio-jsonrpc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The standard input and output of the process as a connection.
+//!
+//! This is for processes driven by their parent over the stdio (eg. plugins or language
+//! servers). The [`Endpoint::stdio`](../endpoint/struct.Endpoint.html#method.stdio) constructor
+//! builds the endpoint on top of them directly.
+//!
+//! The standard input can't be reliably read without blocking (it may be a file, a pipe or a
+//! terminal), so [`Stdin`](struct.Stdin.html) reads it in a separate thread. The end of the input
+//! ends the stream of messages, so the endpoint shuts down cleanly once the parent closes its end
+//! (unless a [`Client`](../endpoint/struct.Client.html) is still kept around).
+//!
+//! Writes to [`Stdout`](struct.Stdout.html) are blocking, but each message is flushed right away.
+//! Once the future returned from the endpoint's `start` resolves, everything is written out and
+//! the process can exit.
+//!
+//! ```rust,no_run
+//! # extern crate tokio_core;
+//! # extern crate tokio_jsonrpc;
+//! # use tokio_core::reactor::Core;
+//! # use tokio_jsonrpc::{Endpoint, LineCodec};
+//! # use tokio_jsonrpc::server::Empty;
+//! # fn main() {
+//! let mut core = Core::new().unwrap();
+//! let handle = core.handle();
+//! // Use a real server here
+//! let (_client, finished) = Endpoint::stdio(LineCodec::new(), Empty).start(&handle);
+//! core.run(finished).unwrap();
+//! # }
+//! ```
+
+use std::io::{self, Error as IoError, ErrorKind, Read, Result as IoResult, Write};
+use std::thread;
+
+use futures::{Async, Future, Poll, Sink, Stream};
+use futures::sync::mpsc::{channel, Receiver};
+use tokio_io::{AsyncRead, AsyncWrite};
+
+/// How much to read from the input at once.
+const CHUNK: usize = 8192;
+
+/// The standard input of the process, readable without blocking.
+///
+/// There should be only one of them in the process, as they would take the input from each
+/// other.
+///
+/// The reading thread is left behind if this is dropped before the end of the input, until more
+/// input comes (or until the process exits).
+#[derive(Debug)]
+pub struct Stdin {
+    chunks: Receiver<IoResult<Vec<u8>>>,
+    chunk: Vec<u8>,
+    position: usize,
+}
+
+impl Stdin {
+    /// Reads the reader in a new thread.
+    fn spawn<R: Read + Send + 'static>(mut reader: R) -> Self {
+        // Only one chunk in flight, we don't want to read ahead more than needed
+        let (mut sender, chunks) = channel(0);
+        thread::spawn(move || loop {
+            let mut chunk = vec![0; CHUNK];
+            let result = match reader.read(&mut chunk) {
+                // The end of input, dropping the sender ends the stream
+                Ok(0) => return,
+                Ok(len) => {
+                    chunk.truncate(len);
+                    Ok(chunk)
+                },
+                Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+                Err(e) => Err(e),
+            };
+            let failed = result.is_err();
+            sender = match sender.send(result).wait() {
+                Ok(sender) => sender,
+                // Nobody is interested in the input any more
+                Err(_) => return,
+            };
+            if failed {
+                return;
+            }
+        });
+        Stdin {
+            chunks,
+            chunk: Vec::new(),
+            position: 0,
+        }
+    }
+}
+
+impl Read for Stdin {
+    fn read(&mut self, buf: &mut [u8]) -> IoResult<usize> {
+        while self.position == self.chunk.len() {
+            match self.chunks.poll() {
+                Ok(Async::Ready(Some(Ok(chunk)))) => {
+                    self.chunk = chunk;
+                    self.position = 0;
+                },
+                Ok(Async::Ready(Some(Err(e)))) => return Err(e),
+                Ok(Async::Ready(None)) => return Ok(0),
+                Ok(Async::NotReady) => return Err(ErrorKind::WouldBlock.into()),
+                Err(()) => unreachable!("Receiver never fails"),
+            }
+        }
+        let len = (&self.chunk[self.position..]).read(buf)?;
+        self.position += len;
+        Ok(len)
+    }
+}
+
+impl AsyncRead for Stdin {}
+
+/// Get the standard input of the process.
+///
+/// This starts the thread reading it.
+pub fn stdin() -> Stdin {
+    Stdin::spawn(io::stdin())
+}
+
+/// The standard output of the process.
+#[derive(Debug)]
+pub struct Stdout(io::Stdout);
+
+impl Write for Stdout {
+    fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+        self.0.write(buf)
+    }
+    fn flush(&mut self) -> IoResult<()> {
+        self.0.flush()
+    }
+}
+
+impl AsyncWrite for Stdout {
+    fn shutdown(&mut self) -> Poll<(), IoError> {
+        self.0.flush()?;
+        Ok(Async::Ready(()))
+    }
+}
+
+/// Get the standard output of the process.
+pub fn stdout() -> Stdout {
+    Stdout(io::stdout())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+    use std::sync::{Arc, Mutex};
+
+    use tokio_core::reactor::Core;
+
+    use super::*;
+    use codec::Line;
+    use endpoint::{Endpoint, ServerCtl};
+    use message::{Params, RpcError};
+    use server::Server;
+
+    /// Answers the "test" RPC and keeps running.
+    struct Answer;
+
+    impl Server for Answer {
+        type Success = u32;
+        type RpcCallResult = Result<u32, RpcError>;
+        type NotificationResult = Result<(), ()>;
+        fn rpc(
+            &self, _ctl: &ServerCtl, method: &str, _params: &Option<Params>
+        ) -> Option<Self::RpcCallResult> {
+            assert_eq!("test", method);
+            Some(Ok(42))
+        }
+    }
+
+    /// A writer keeping the output for checking.
+    #[derive(Clone, Default)]
+    struct Output(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Output {
+        fn write(&mut self, buf: &[u8]) -> IoResult<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+        fn flush(&mut self) -> IoResult<()> {
+            Ok(())
+        }
+    }
+
+    impl AsyncWrite for Output {
+        fn shutdown(&mut self) -> Poll<(), IoError> {
+            Ok(Async::Ready(()))
+        }
+    }
+
+    /// Data larger than a chunk is read whole, in order.
+    #[test]
+    fn read_chunks() {
+        let data: Vec<u8> = (0..3 * CHUNK + 10).map(|i| i as u8).collect();
+        let input = Stdin::spawn(Cursor::new(data.clone()));
+        let (_input, read) = ::tokio_io::io::read_to_end(input, Vec::new())
+            .wait()
+            .unwrap();
+        assert_eq!(data, read);
+    }
+
+    /// The endpoint answers everything and finishes cleanly once the input ends.
+    #[test]
+    fn endpoint_eof() {
+        let input = b"{\"jsonrpc\": \"2.0\", \"method\": \"test\", \"id\": 1}\n\
+                      {\"jsonrpc\": \"2.0\", \"method\": \"test\", \"id\": 2}\n";
+        let output = Output::default();
+        let mut core = Core::new().unwrap();
+        let (client, finished) = Endpoint::from_halves(
+            Stdin::spawn(Cursor::new(input.to_vec())),
+            output.clone(),
+            Line::new(),
+            Answer,
+        ).start(&core.handle());
+        drop(client);
+        core.run(finished).unwrap();
+        let written = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        assert_eq!(
+            "{\"jsonrpc\":\"2.0\",\"result\":42,\"id\":1}\n\
+             {\"jsonrpc\":\"2.0\",\"result\":42,\"id\":2}\n",
+            written
+        );
+    }
+}
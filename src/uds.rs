@@ -0,0 +1,176 @@
+// Copyright 2017 tokio-jsonrpc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! Endpoints over Unix domain sockets.
+//!
+//! Available on Unix systems with the `uds` feature. The [`listen`](fn.listen.html) function
+//! accepts connections and [`connect`](fn.connect.html) dials one. Both produce the
+//! [`Endpoint`](../endpoint/struct.Endpoint.html) builders, to be configured and started.
+//!
+//! The endpoints know the credentials of the process on the other side of the socket. The server
+//! can get them through
+//! [`ServerCtl::peer_credentials`](../endpoint/struct.ServerCtl.html#method.peer_credentials) and
+//! decide what the peer is allowed to do. The process ID is known on Linux and Android only.
+//!
+//! ```rust,no_run
+//! # extern crate futures;
+//! # extern crate tokio_core;
+//! # extern crate tokio_jsonrpc;
+//! # use futures::Stream;
+//! # use tokio_core::reactor::Core;
+//! # use tokio_jsonrpc::LineCodec;
+//! # use tokio_jsonrpc::server::Empty;
+//! # use tokio_jsonrpc::uds;
+//! # fn main() {
+//! let mut core = Core::new().unwrap();
+//! let handle = core.handle();
+//! // Use a real server here, checking the credentials
+//! let endpoints = uds::listen("/run/service.sock", LineCodec::new(), || Empty).unwrap();
+//! let accepted = endpoints.for_each(|endpoint| {
+//!     match endpoint {
+//!         Ok(endpoint) => drop(endpoint.start(&handle)),
+//!         // Unknown peer, its connection is dropped, but the others are still accepted
+//!         Err(e) => eprintln!("Refused a connection: {}", e),
+//!     }
+//!     Ok(())
+//! });
+//! core.run(accepted).unwrap();
+//! # }
+//! ```
+
+use std::io::Error as IoError;
+use std::path::Path;
+
+use futures::{Future, Stream};
+// The framing moved to tokio-codec, but the crate and its users build on the one in tokio_io
+#[allow(deprecated)]
+use tokio_io::codec::{Decoder, Encoder, Framed};
+use tokio_uds::{UnixListener, UnixStream};
+
+use endpoint::{Endpoint, PeerCredentials, Received};
+use message::Message;
+use server::Server;
+
+/// The connection over a Unix domain socket.
+#[allow(deprecated)]
+pub type Connection<Codec> = Framed<UnixStream, Codec>;
+
+/// The endpoints of the accepted connections.
+///
+/// Each item is the endpoint of one connection, or the error of getting the credentials of its
+/// peer.
+pub type Incoming<Codec, RpcServer> = Box<
+    dyn Stream<Item = Result<Endpoint<Connection<Codec>, RpcServer>, IoError>, Error = IoError>,
+>;
+
+/// The future of the endpoint of a dialed connection.
+pub type Connecting<Codec, RpcServer> =
+    Box<dyn Future<Item = Endpoint<Connection<Codec>, RpcServer>, Error = IoError>>;
+
+/// Get the credentials of the process on the other side of the socket.
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub fn peer_credentials(stream: &UnixStream) -> Result<PeerCredentials, IoError> {
+    use std::mem;
+    use std::os::unix::io::AsRawFd;
+
+    use libc::{c_void, getsockopt, socklen_t, ucred, SOL_SOCKET, SO_PEERCRED};
+
+    // The tokio-uds one doesn't provide the PID
+    let mut cred = ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = mem::size_of::<ucred>() as socklen_t;
+    let result = unsafe {
+        getsockopt(
+            stream.as_raw_fd(),
+            SOL_SOCKET,
+            SO_PEERCRED,
+            &mut cred as *mut ucred as *mut c_void,
+            &mut len,
+        )
+    };
+    if result == 0 {
+        Ok(PeerCredentials {
+            pid: Some(cred.pid as u32),
+            uid: cred.uid,
+            gid: cred.gid,
+        })
+    } else {
+        Err(IoError::last_os_error())
+    }
+}
+
+/// Get the credentials of the process on the other side of the socket.
+#[cfg(not(any(target_os = "linux", target_os = "android")))]
+pub fn peer_credentials(stream: &UnixStream) -> Result<PeerCredentials, IoError> {
+    let cred = stream.peer_cred()?;
+    Ok(PeerCredentials {
+        pid: None,
+        uid: cred.uid,
+        gid: cred.gid,
+    })
+}
+
+/// Build the endpoint on a connected stream, with the credentials of the peer.
+fn endpoint<Codec, RpcServer>(
+    stream: UnixStream, codec: Codec, server: RpcServer
+) -> Result<Endpoint<Connection<Codec>, RpcServer>, IoError>
+where
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Send + 'static,
+    RpcServer: Server + 'static,
+{
+    let credentials = peer_credentials(&stream)?;
+    Ok(Endpoint::from_io(stream, codec, server).peer_credentials(credentials))
+}
+
+/// Listen on the socket at the path.
+///
+/// Each accepted connection produces an endpoint, with a server created by `new_server` and
+/// framed by a copy of the codec. The endpoints only need to be started. If the credentials of
+/// the peer can't be read, the connection produces the error instead and is dropped (unless the
+/// error is propagated), but the stream goes on accepting. The stream itself fails only if
+/// accepting a connection fails.
+///
+/// The socket file must not exist yet and it is not removed once the listener is dropped.
+pub fn listen<P, Codec, RpcServer, NewServer>(
+    path: P, codec: Codec, mut new_server: NewServer
+) -> Result<Incoming<Codec, RpcServer>, IoError>
+where
+    P: AsRef<Path>,
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Clone + Send + 'static,
+    RpcServer: Server + 'static,
+    NewServer: FnMut() -> RpcServer + 'static,
+{
+    let listener = UnixListener::bind(path)?;
+    let endpoints = listener
+        .incoming()
+        .map(move |stream| endpoint(stream, codec.clone(), new_server()));
+    Ok(Box::new(endpoints))
+}
+
+/// Connect to the socket at the path.
+///
+/// The resulting endpoint runs the server and knows the credentials of the listening process.
+pub fn connect<P, Codec, RpcServer>(
+    path: P, codec: Codec, server: RpcServer
+) -> Connecting<Codec, RpcServer>
+where
+    P: AsRef<Path>,
+    Codec: Decoder<Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    <Codec as Decoder>::Item: Into<Received>,
+    Codec: Send + 'static,
+    RpcServer: Server + 'static,
+{
+    let connected = UnixStream::connect(path).and_then(|stream| endpoint(stream, codec, server));
+    Box::new(connected)
+}
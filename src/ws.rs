@@ -0,0 +1,246 @@
+// Copyright 2017 tokio-jsonrpc Developers
+//
+// Licensed under the Apache License, Version 2.0, <LICENSE-APACHE or
+// http://apache.org/licenses/LICENSE-2.0> or the MIT license <LICENSE-MIT or
+// http://opensource.org/licenses/MIT>, at your option. This file may not be
+// copied, modified, or distributed except according to those terms.
+
+//! The WebSocket transport.
+//!
+//! Available with the `websocket` feature. The [`WebSocket`](struct.WebSocket.html) adapts a
+//! WebSocket connection (of the [websocket](https://docs.rs/websocket) crate) to the stream and
+//! sink of messages the [`Endpoint`](../endpoint/struct.Endpoint.html) expects. Each message is
+//! sent as a single text frame. Both text and binary frames are accepted from the peer.
+//!
+//! The control frames are handled by the adapter. Pings are answered with pongs and when the peer
+//! closes the connection, the close frame is answered and the stream of messages ends. Closing
+//! the sink sends a close frame to the peer.
+//!
+//! The [`connect`](fn.connect.html) and [`connect_on`](fn.connect_on.html) functions do the
+//! handshake from the dialing side, [`accept`](fn.accept.html) from the accepting one.
+//!
+//! ```rust,no_run
+//! # extern crate tokio_core;
+//! # extern crate tokio_jsonrpc;
+//! # extern crate futures;
+//! # use futures::{Future, Stream};
+//! # use tokio_core::reactor::Core;
+//! # use tokio_core::net::TcpListener;
+//! # use tokio_jsonrpc::Endpoint;
+//! # use tokio_jsonrpc::server::Empty;
+//! # use tokio_jsonrpc::ws;
+//! # fn main() {
+//! let mut core = Core::new().unwrap();
+//! let handle = core.handle();
+//! let listener = TcpListener::bind(&"127.0.0.1:2346".parse().unwrap(), &handle).unwrap();
+//! let connections = listener.incoming().for_each(|(stream, _)| {
+//!     let handle = handle.clone();
+//!     ws::accept(stream)
+//!         .map(move |socket| {
+//!             // Use a real server here
+//!             Endpoint::new(socket, Empty).start(&handle);
+//!         })
+//!         // Don't let one failed handshake end the whole server
+//!         .or_else(|_| Ok(()))
+//! });
+//! core.run(connections).unwrap();
+//! # }
+//! ```
+
+use std::io::{Error as IoError, ErrorKind};
+
+use futures::{future, Async, AsyncSink, Future, Poll, Sink, StartSend, Stream};
+use serde_json::ser::to_string;
+use tokio_io::{AsyncRead, AsyncWrite};
+use websocket::{ClientBuilder, OwnedMessage, WebSocketError};
+use websocket::async::{Client, TcpStream};
+use websocket::async::server::IntoWs;
+
+use message::{Message, Parsed, Parser};
+
+/// Unwraps the I/O errors, the protocol errors are invalid data.
+fn ws_error(e: WebSocketError) -> IoError {
+    match e {
+        WebSocketError::IoError(e) => e,
+        e => IoError::new(ErrorKind::InvalidData, e),
+    }
+}
+
+/// A future of the WebSocket connection ready to be used by an endpoint.
+pub type Connecting<S> = Box<dyn Future<Item = WebSocket<S>, Error = IoError> + Send>;
+
+/// A WebSocket connection as a stream and sink of JSON RPC messages.
+///
+/// See the [module documentation](index.html) for details.
+pub struct WebSocket<S> {
+    client: Client<S>,
+    parser: Parser,
+    /// A control frame (pong or close) waiting to be sent.
+    control: Option<OwnedMessage>,
+    /// The close frame was already sent (or queued to be sent).
+    closed: bool,
+}
+
+impl<S: AsyncRead + AsyncWrite> WebSocket<S> {
+    /// Wraps an established WebSocket connection.
+    pub fn new(client: Client<S>) -> Self {
+        Self::with_parser(client, Parser::new())
+    }
+    /// Wraps an established WebSocket connection, parsing the messages with the given parser.
+    pub fn with_parser(client: Client<S>, parser: Parser) -> Self {
+        WebSocket {
+            client,
+            parser,
+            control: None,
+            closed: false,
+        }
+    }
+    /// Returns the underlying WebSocket connection.
+    ///
+    /// A control frame not yet sent is lost.
+    pub fn into_inner(self) -> Client<S> {
+        self.client
+    }
+    /// Queues a close frame, unless one was already sent.
+    fn queue_close(&mut self) {
+        if !self.closed {
+            self.control = Some(OwnedMessage::Close(None));
+            self.closed = true;
+        }
+    }
+    /// Hands the pending control frame (if any) over to the connection.
+    fn send_control(&mut self) -> Poll<(), IoError> {
+        if let Some(frame) = self.control.take() {
+            if let AsyncSink::NotReady(frame) = self.client.start_send(frame).map_err(ws_error)? {
+                self.control = Some(frame);
+                return Ok(Async::NotReady);
+            }
+        }
+        Ok(Async::Ready(()))
+    }
+    /// Tries to push the pending control frame out, without waiting for it.
+    fn flush_control(&mut self) -> Result<(), IoError> {
+        if self.send_control()?.is_ready() {
+            self.client.poll_complete().map_err(ws_error)?;
+        }
+        Ok(())
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> Stream for WebSocket<S> {
+    type Item = Parsed;
+    type Error = IoError;
+    fn poll(&mut self) -> Poll<Option<Parsed>, IoError> {
+        loop {
+            let frame = match self.client.poll().map_err(ws_error)? {
+                Async::Ready(Some(frame)) => frame,
+                Async::Ready(None) => return Ok(Async::Ready(None)),
+                Async::NotReady => return Ok(Async::NotReady),
+            };
+            match frame {
+                OwnedMessage::Text(text) => {
+                    return Ok(Async::Ready(Some(self.parser.parse_slice(text.as_bytes()))))
+                },
+                OwnedMessage::Binary(data) => {
+                    return Ok(Async::Ready(Some(self.parser.parse_slice(&data))))
+                },
+                OwnedMessage::Ping(data) => {
+                    // Only the latest ping needs an answer, so an older unsent pong can go
+                    if !self.closed {
+                        self.control = Some(OwnedMessage::Pong(data));
+                        self.flush_control()?;
+                    }
+                },
+                OwnedMessage::Pong(_) => (),
+                OwnedMessage::Close(_) => {
+                    self.queue_close();
+                    self.flush_control()?;
+                    return Ok(Async::Ready(None));
+                },
+            }
+        }
+    }
+}
+
+impl<S: AsyncRead + AsyncWrite> Sink for WebSocket<S> {
+    type SinkItem = Message;
+    type SinkError = IoError;
+    fn start_send(&mut self, msg: Message) -> StartSend<Message, IoError> {
+        if self.send_control()?.is_not_ready() {
+            return Ok(AsyncSink::NotReady(msg));
+        }
+        let text = to_string(&msg).map_err(|e| IoError::new(ErrorKind::InvalidData, e))?;
+        match self.client
+            .start_send(OwnedMessage::Text(text))
+            .map_err(ws_error)?
+        {
+            AsyncSink::Ready => Ok(AsyncSink::Ready),
+            AsyncSink::NotReady(_) => Ok(AsyncSink::NotReady(msg)),
+        }
+    }
+    fn poll_complete(&mut self) -> Poll<(), IoError> {
+        if self.send_control()?.is_not_ready() {
+            return Ok(Async::NotReady);
+        }
+        self.client.poll_complete().map_err(ws_error)
+    }
+    fn close(&mut self) -> Poll<(), IoError> {
+        self.queue_close();
+        if self.send_control()?.is_not_ready() {
+            return Ok(Async::NotReady);
+        }
+        self.client.close().map_err(ws_error)
+    }
+}
+
+/// Connects to a `ws://` URL.
+///
+/// This opens a new TCP connection and does the WebSocket handshake on it. Secure (`wss://`)
+/// connections are not supported by this function, but an already established TLS connection can
+/// be passed to [`connect_on`](fn.connect_on.html).
+pub fn connect(url: &str) -> Connecting<TcpStream> {
+    let builder = match ClientBuilder::new(url) {
+        Ok(builder) => builder,
+        Err(e) => return Box::new(future::err(IoError::new(ErrorKind::InvalidInput, e))),
+    };
+    let connected = builder
+        .async_connect_insecure()
+        .map(|(client, _headers)| WebSocket::new(client))
+        .map_err(ws_error);
+    Box::new(connected)
+}
+
+/// Does the WebSocket handshake for the `url` on an already established connection.
+pub fn connect_on<S>(url: &str, stream: S) -> Connecting<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let builder = match ClientBuilder::new(url) {
+        Ok(builder) => builder,
+        Err(e) => return Box::new(future::err(IoError::new(ErrorKind::InvalidInput, e))),
+    };
+    let connected = builder
+        .async_connect_on(stream)
+        .map(|(client, _headers)| WebSocket::new(client))
+        .map_err(ws_error);
+    Box::new(connected)
+}
+
+/// Accepts the WebSocket handshake on a connection from the dialing side.
+///
+/// Every upgrade request is accepted, regardless of its URL or protocols. To decide based on them,
+/// do the handshake through the [websocket](https://docs.rs/websocket) crate directly and use
+/// [`WebSocket::new`](struct.WebSocket.html#method.new) on the result.
+pub fn accept<S>(stream: S) -> Connecting<S>
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let accepted = stream
+        .into_ws()
+        .map_err(|(_stream, _request, _buffer, e)| {
+            IoError::new(ErrorKind::InvalidData, e.to_string())
+        })
+        .and_then(|upgrade| upgrade.accept().map_err(ws_error))
+        .map(|(client, _headers)| WebSocket::new(client));
+    Box::new(accepted)
+}
@@ -6,25 +6,55 @@
 // copied, modified, or distributed except according to those terms.
 
 extern crate futures;
+#[cfg(feature = "hyper")]
+extern crate hyper;
 #[macro_use]
 extern crate serde_json;
 extern crate tokio_core;
 extern crate tokio_io;
+#[macro_use]
 extern crate tokio_jsonrpc;
+#[cfg(feature = "websocket")]
+extern crate websocket;
 
 use std::time::Duration;
-use std::io::Error as IoError;
+use std::io::{Error as IoError, ErrorKind, Write};
 use std::cell::Cell;
 use std::rc::Rc;
+#[cfg(feature = "tokio-process")]
+use std::process::{Command, Stdio};
+use std::thread;
 
-use futures::{Future, IntoFuture, Stream};
+use futures::{Future, IntoFuture, Sink, Stream};
 use tokio_core::reactor::{Core, Handle, Timeout};
 use tokio_core::net::{TcpListener, TcpStream};
 use tokio_io::codec::Framed;
 use tokio_io::AsyncRead;
-use serde_json::{from_value, Value};
+use serde_json::Value;
+
+use tokio_io::codec::{Decoder, Encoder};
 
-use tokio_jsonrpc::{Client, Endpoint, LineCodec, RpcError, Server, ServerCtl};
+use tokio_jsonrpc::{BoundaryCodec, Client, Endpoint, LineCodec, Parsed, RpcError, Server,
+                    ServerCtl};
+use tokio_jsonrpc::codec::{AutoDetect, ContentLength, LengthPrefixed, Netstring, RawLine};
+use tokio_jsonrpc::endpoint::{DuplicateId, IdMatching, IdleTimeout};
+use tokio_jsonrpc::message::{Id, Message, NotificationRef, Params, Parser, RequestRef, Response,
+                             SequentialIds, Version};
+use tokio_jsonrpc::server::Empty;
+#[cfg(feature = "cbor")]
+use tokio_jsonrpc::codec::Cbor;
+#[cfg(feature = "msgpack")]
+use tokio_jsonrpc::codec::MessagePack;
+#[cfg(feature = "hyper")]
+use tokio_jsonrpc::http::{HttpClient, HttpServer};
+#[cfg(feature = "tokio-process")]
+use tokio_jsonrpc::process;
+#[cfg(all(unix, feature = "uds"))]
+use tokio_jsonrpc::uds;
+#[cfg(feature = "websocket")]
+use tokio_jsonrpc::ws;
+#[cfg(feature = "websocket")]
+use websocket::{ClientBuilder, OwnedMessage, WebSocketError};
 
 /// A test server
 ///
@@ -37,7 +67,7 @@ impl Server for AnswerServer {
     type RpcCallResult = Result<u32, RpcError>;
     type NotificationResult = Result<(), ()>;
     fn rpc(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::RpcCallResult> {
         ctl.terminate();
         assert_eq!(method, "test");
@@ -45,7 +75,7 @@ impl Server for AnswerServer {
         Some(Ok(42))
     }
     fn notification(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::NotificationResult> {
         ctl.terminate();
         assert_eq!(method, "notif");
@@ -59,11 +89,7 @@ impl Server for AnswerServer {
 /// Create a reactor, set a safety timeout (if the test doesn't finish in 15 seconds, panic) and
 /// provide two connected TCP streams. We could use unix socket pair, but that wouldn't work on
 /// windows, so we just connect on 127.0.0.1.
-fn prepare() -> (
-    Core,
-    Framed<TcpStream, LineCodec>,
-    Framed<TcpStream, LineCodec>,
-) {
+fn prepare_raw() -> (Core, TcpStream, TcpStream) {
     let mut reactor = Core::new().unwrap();
     let handle = reactor.handle();
     // Kill the test if it gets stuck
@@ -85,6 +111,16 @@ fn prepare() -> (
     // Wait for both of them to be connected
     let (s1, s2) = reactor.run(server_finished.join(client_finished)).unwrap();
     // And return everything
+    (reactor, s1, s2)
+}
+
+/// Like `prepare_raw`, but the streams are already framed.
+fn prepare() -> (
+    Core,
+    Framed<TcpStream, LineCodec>,
+    Framed<TcpStream, LineCodec>,
+) {
+    let (reactor, s1, s2) = prepare_raw();
     (
         reactor,
         s1.framed(LineCodec::new()),
@@ -128,6 +164,842 @@ fn rpc_answer() {
     reactor.run(all).unwrap();
 }
 
+/// Run a single RPC call over the connection framed by the given codec.
+fn rpc_over<Codec>(codec: Codec)
+where
+    Codec: Decoder<Item = Parsed, Error = IoError> + Encoder<Item = Message, Error = IoError>,
+    Codec: Clone + Send + 'static,
+{
+    rpc_between(codec.clone(), codec);
+}
+
+/// Run a single RPC call, with each side framing the connection by a different codec.
+fn rpc_between<ServerCodec, ClientCodec>(server_codec: ServerCodec, client_codec: ClientCodec)
+where
+    ServerCodec: Decoder<Item = Parsed, Error = IoError>,
+    ServerCodec: Encoder<Item = Message, Error = IoError> + Send + 'static,
+    ClientCodec: Decoder<Item = Parsed, Error = IoError>,
+    ClientCodec: Encoder<Item = Message, Error = IoError> + Send + 'static,
+{
+    let (mut reactor, s1, s2) = prepare_raw();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let (_client, server_finished) =
+            process_start(Endpoint::from_io(s1, server_codec, AnswerServer).start(&handle));
+        let (client, client_endpoint_finished) =
+            process_start(Endpoint::from_io(s2, client_codec, Empty).start(&handle));
+        client
+            .call("test".to_owned(), None, None)
+            .and_then(|(_client, answered)| answered)
+            .map(|response| assert_eq!(json!(42), response.unwrap().result.unwrap()))
+            .join3(server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// The endpoint works unchanged on top of the header framing.
+#[test]
+fn rpc_content_length() {
+    rpc_over(ContentLength::new());
+}
+
+/// The auto-detecting server talks to clients using any of the framings it knows.
+#[test]
+fn rpc_auto_detect() {
+    rpc_between(AutoDetect::new(), LineCodec::new());
+    rpc_between(AutoDetect::new(), BoundaryCodec::new());
+    rpc_between(AutoDetect::new(), ContentLength::new());
+}
+
+/// The endpoint works unchanged on top of the length-prefixed framing.
+#[test]
+fn rpc_length_prefixed() {
+    rpc_over(LengthPrefixed::new());
+}
+
+/// The endpoint works unchanged on top of netstrings.
+#[test]
+fn rpc_netstring() {
+    rpc_over(Netstring::new());
+}
+
+/// The endpoint works unchanged on top of the CBOR codec.
+#[cfg(feature = "cbor")]
+#[test]
+fn rpc_cbor() {
+    rpc_over(Cbor::new());
+}
+
+/// The endpoint works unchanged on top of the MessagePack codec.
+#[cfg(feature = "msgpack")]
+#[test]
+fn rpc_msgpack() {
+    rpc_over(MessagePack::new());
+}
+
+/// A server for the raw frames.
+///
+/// It sums the numbers passed to `"sum"`, decoding them right from the raw parameters, and
+/// terminates on the `"done"` notification. The owned callbacks must not be used.
+struct RawServer;
+
+impl Server for RawServer {
+    type Success = u64;
+    type RpcCallResult = Result<u64, RpcError>;
+    type NotificationResult = Result<(), ()>;
+    fn rpc(
+        &self, _ctl: &ServerCtl, method: &str, _params: &Option<Params>
+    ) -> Option<Self::RpcCallResult> {
+        panic!("Owned request {}", method);
+    }
+    fn rpc_ref(&self, _ctl: &ServerCtl, request: &RequestRef) -> Option<Self::RpcCallResult> {
+        match &*request.method {
+            "sum" => {
+                let numbers = jsonrpc_params!(request.params, raw Vec<u64>);
+                Some(Ok(numbers.iter().sum()))
+            },
+            _ => None,
+        }
+    }
+    fn notification_ref(
+        &self, ctl: &ServerCtl, notification: &NotificationRef
+    ) -> Option<Self::NotificationResult> {
+        ctl.terminate();
+        assert_eq!("done", notification.method);
+        Some(Ok(()))
+    }
+}
+
+/// The endpoint parses the raw frames itself and the server gets the borrowed requests.
+///
+/// The server side is framed either as a whole or by the separate halves.
+fn raw_frames(halves: bool) {
+    let (mut reactor, s1, s2) = prepare_raw();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let server = if halves {
+            let (reader, writer) = s1.split();
+            Endpoint::from_halves(reader, writer, RawLine::new(), RawServer).start(&handle)
+        } else {
+            Endpoint::from_io(s1, RawLine::new(), RawServer).start(&handle)
+        };
+        let (_client, server_finished) = process_start(server);
+        let (client, client_endpoint_finished) =
+            process_start(Endpoint::from_io(s2, LineCodec::new(), Empty).start(&handle));
+        let call = |client: Client, method: &str, params: Vec<Value>| {
+            client
+                .call(method.to_owned(), Some(Params::Positional(params)), None)
+                .and_then(|(client, answered)| {
+                    answered.map(|response| (client, response.unwrap().result))
+                })
+        };
+        call(client, "sum", vec![json!(1), json!(2), json!(3)])
+            .and_then(move |(client, result)| {
+                assert_eq!(json!(6), result.unwrap());
+                call(client, "sum", vec![json!("x")])
+            })
+            .and_then(move |(client, result)| {
+                assert_eq!(-32_602, result.unwrap_err().code);
+                call(client, "product", vec![])
+            })
+            .and_then(|(client, result)| {
+                assert_eq!(-32_601, result.unwrap_err().code);
+                client.notify("done".to_owned(), None)
+            })
+            // Let the client go, so its endpoint may terminate
+            .map(drop)
+            .join3(server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+#[test]
+fn rpc_raw_frames() {
+    raw_frames(false);
+}
+
+/// The raw frames work over the separate halves too (like pipes or stdio).
+#[test]
+fn rpc_raw_frames_halves() {
+    raw_frames(true);
+}
+
+/// The endpoint works on top of a WebSocket connection, set up from both sides.
+#[cfg(feature = "websocket")]
+#[test]
+fn rpc_websocket() {
+    let (mut reactor, s1, s2) = prepare_raw();
+    let handshake = ws::accept(s1).join(ws::connect_on("ws://127.0.0.1/", s2));
+    let (server_ws, client_ws) = reactor.run(handshake).unwrap();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let (_client, server_finished) =
+            process_start(Endpoint::new(server_ws, AnswerServer).start(&handle));
+        let (client, client_endpoint_finished) =
+            process_start(Endpoint::new(client_ws, Empty).start(&handle));
+        client
+            .call("test".to_owned(), None, None)
+            .and_then(|(_client, answered)| answered)
+            .map(|response| assert_eq!(json!(42), response.unwrap().result.unwrap()))
+            .join3(server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// Dialing a URL opens the connection and does the handshake.
+#[cfg(feature = "websocket")]
+#[test]
+fn websocket_connect() {
+    let mut reactor = Core::new().unwrap();
+    let handle = reactor.handle();
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+    let url = format!("ws://{}/", listener.local_addr().unwrap());
+    let accepted = listener
+        .incoming()
+        .into_future()
+        .map_err(|(err, _incoming)| err)
+        .and_then(|(stream, _incoming)| ws::accept(stream.unwrap().0));
+    let (server_ws, client_ws) = reactor.run(accepted.join(ws::connect(&url))).unwrap();
+    let notif = Message::notification("notif".to_owned(), None);
+    let sent = client_ws.send(notif.clone());
+    let received = server_ws.into_future().map_err(|(err, _server_ws)| err);
+    let (_client_ws, (parsed, _server_ws)) = reactor.run(sent.join(received)).unwrap();
+    assert_eq!(Some(Ok(notif)), parsed);
+}
+
+/// The adapter answers pings and closing of the connection, without passing them on.
+#[cfg(feature = "websocket")]
+#[test]
+fn websocket_control() {
+    let (mut reactor, s1, s2) = prepare_raw();
+    let raw_client = ClientBuilder::new("ws://127.0.0.1/")
+        .unwrap()
+        .async_connect_on(s2)
+        .map(|(client, _headers)| client)
+        .map_err(|e| IoError::new(ErrorKind::InvalidData, e));
+    let (server_ws, raw_client) = reactor.run(ws::accept(s1).join(raw_client)).unwrap();
+    let frames = vec![
+        OwnedMessage::Ping(b"hello".to_vec()),
+        OwnedMessage::Pong(b"unsolicited".to_vec()),
+        OwnedMessage::Close(None),
+    ];
+    let peer = raw_client
+        .send_all(futures::stream::iter_ok::<_, WebSocketError>(frames))
+        .and_then(|(raw_client, _frames)| raw_client.collect())
+        .map_err(|e| IoError::new(ErrorKind::InvalidData, e));
+    let (received, answers) = reactor.run(server_ws.collect().join(peer)).unwrap();
+    assert!(received.is_empty());
+    assert_eq!(
+        vec![
+            OwnedMessage::Pong(b"hello".to_vec()),
+            OwnedMessage::Close(None),
+        ],
+        answers
+    );
+}
+
+/// Start an HTTP server with the `AnswerServer` and return the URL to post to.
+#[cfg(feature = "hyper")]
+fn http_server(parser: Parser) -> (Core, hyper::Uri) {
+    let reactor = Core::new().unwrap();
+    let handle = reactor.handle();
+    // Kill the test if it gets stuck
+    let timeout = Timeout::new(Duration::new(15, 0), &handle)
+        .unwrap()
+        .then(|_| -> Result<(), ()> { panic!("Timeout happened") });
+    handle.spawn(timeout);
+    let listener = TcpListener::bind(&"127.0.0.1:0".parse().unwrap(), &handle).unwrap();
+    let uri = format!("http://{}/", listener.local_addr().unwrap())
+        .parse()
+        .unwrap();
+    let server = HttpServer::new(AnswerServer, &handle).parser(parser);
+    let handle_cloned = handle.clone();
+    let connections = listener.incoming().for_each(move |(stream, _)| {
+        handle_cloned.spawn(server.serve(stream).map_err(|e| panic!("Error: {}", e)));
+        Ok(())
+    });
+    handle.spawn(connections.map_err(|e| panic!("Error: {}", e)));
+    (reactor, uri)
+}
+
+/// Calls and notifications posted over HTTP reach the server.
+#[cfg(feature = "hyper")]
+#[test]
+fn rpc_http() {
+    let (mut reactor, uri) = http_server(Parser::new());
+    let client = HttpClient::new(uri).id_generator(SequentialIds::new(1));
+    let response = reactor
+        .run(client.call("test".to_owned(), None, None))
+        .unwrap()
+        .unwrap();
+    assert_eq!(Id::Number(1), response.id);
+    assert_eq!(json!(42), response.result.unwrap());
+    reactor
+        .run(client.notify("notif".to_owned(), None))
+        .unwrap();
+}
+
+/// The HTTP server answers batches, sends nothing to notifications and refuses other methods
+/// than POST.
+#[cfg(feature = "hyper")]
+#[test]
+fn http_answers() {
+    let (mut reactor, uri) = http_server(Parser::new());
+    let client = HttpClient::new(uri.clone());
+    let batch = Message::Batch(vec![
+        Message::request_with_id("test".to_owned(), None, Id::Number(1)),
+        Message::notification("notif".to_owned(), None),
+    ]);
+    match reactor.run(client.send(batch)).unwrap() {
+        Some(Ok(Message::Batch(mut answers))) => match answers.pop() {
+            Some(Message::Response(response)) => {
+                assert!(answers.is_empty());
+                assert_eq!(Id::Number(1), response.id);
+                assert_eq!(json!(42), response.result.unwrap());
+            },
+            other => panic!("Unexpected answer {:?}", other),
+        },
+        other => panic!("Unexpected answer {:?}", other),
+    }
+    let notif = Message::notification("notif".to_owned(), None);
+    assert_eq!(None, reactor.run(client.send(notif)).unwrap());
+    let broken = reactor.run(client.send(Message::Batch(Vec::new()))).unwrap();
+    match broken {
+        Some(Ok(Message::Response(response))) => assert_eq!(Id::Null, response.id),
+        other => panic!("Unexpected answer {:?}", other),
+    }
+    let got = reactor.run(hyper::Client::new().get(uri)).unwrap();
+    assert_eq!(hyper::StatusCode::METHOD_NOT_ALLOWED, got.status());
+}
+
+/// The HTTP client returns the errors the server couldn't tie to the call and gives up on a
+/// server that doesn't answer.
+#[cfg(feature = "hyper")]
+#[test]
+fn http_client_errors() {
+    let (mut reactor, uri) = http_server(Parser::new().max_size(Some(10)));
+    let client = HttpClient::new(uri);
+    let response = reactor
+        .run(client.call("test".to_owned(), None, None))
+        .unwrap()
+        .unwrap();
+    assert_eq!(Id::Null, response.id);
+    assert_eq!(-32_600, response.result.unwrap_err().code);
+
+    // This one accepts the connections, but never answers
+    let silent = ::std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let uri = format!("http://{}/", silent.local_addr().unwrap())
+        .parse()
+        .unwrap();
+    let call = HttpClient::new(uri).call("test".to_owned(), None, Some(Duration::from_millis(500)));
+    assert!(reactor.run(call).unwrap().is_none());
+}
+
+/// The endpoint talks to a child process.
+///
+/// The child is `cat`, so it sends our own call back to us, the server answers it and the answer
+/// is sent back to us too. Once the client is dropped, the endpoint ends and the child is reaped.
+#[cfg(all(unix, feature = "tokio-process"))]
+#[test]
+fn rpc_child() {
+    let mut reactor = Core::new().unwrap();
+    let handle = reactor.handle();
+    let (client, child) =
+        process::spawn(&mut Command::new("cat"), LineCodec::new(), AnswerServer, &handle).unwrap();
+    let called = client
+        .call("test".to_owned(), None, None)
+        .and_then(|(_client, answered)| answered);
+    let response = reactor.run(called).unwrap().unwrap();
+    assert_eq!(json!(42), response.result.unwrap());
+    reactor.run(child).unwrap();
+}
+
+/// Killing the endpoint kills the child too, killing the child ends the endpoint. The stderr of
+/// the child can be read.
+#[cfg(all(unix, feature = "tokio-process"))]
+#[test]
+fn child_kill() {
+    let mut reactor = Core::new().unwrap();
+    let handle = reactor.handle();
+    let mut command = Command::new("sh");
+    command
+        .args(["-c", "echo started >&2; exec cat"])
+        .stderr(Stdio::piped());
+    let (client, mut child) =
+        process::spawn(&mut command, LineCodec::new(), Empty, &handle).unwrap();
+    let stderr = child.stderr().unwrap();
+    assert!(child.stderr().is_none());
+    let (line, _stderr) = reactor
+        .run(stderr.into_future().map_err(|(e, _stderr)| e))
+        .unwrap();
+    assert_eq!(Some("started".to_owned()), line);
+    client.server_ctl().kill();
+    let status = reactor.run(child).unwrap();
+    assert!(!status.success());
+
+    let (client, child) =
+        process::spawn(&mut Command::new("cat"), LineCodec::new(), Empty, &handle).unwrap();
+    child.kill();
+    let status = reactor.run(child).unwrap();
+    assert!(!status.success());
+    // The endpoint ended with the child, so the call can't be answered
+    let called = client
+        .call("test".to_owned(), None, None)
+        .and_then(|(_client, answered)| answered);
+    assert!(reactor.run(called).is_err());
+}
+
+/// A test server telling the peer what it knows about it.
+///
+/// It answers the `"whoami"` RPC with the peer's PID, UID and GID.
+#[cfg(all(unix, feature = "uds"))]
+struct WhoAmI;
+
+#[cfg(all(unix, feature = "uds"))]
+impl Server for WhoAmI {
+    type Success = Value;
+    type RpcCallResult = Result<Value, RpcError>;
+    type NotificationResult = Result<(), ()>;
+    fn rpc(
+        &self, ctl: &ServerCtl, method: &str, _params: &Option<Params>
+    ) -> Option<Self::RpcCallResult> {
+        assert_eq!(method, "whoami");
+        let credentials = ctl.peer_credentials().unwrap();
+        Some(Ok(json!([credentials.pid, credentials.uid, credentials.gid])))
+    }
+}
+
+/// The endpoints over a Unix domain socket know the credentials of each other.
+#[cfg(all(unix, feature = "uds"))]
+#[test]
+fn rpc_uds() {
+    let path = std::env::temp_dir().join(format!("tokio-jsonrpc-{}.sock", std::process::id()));
+    drop(std::fs::remove_file(&path));
+    let mut reactor = Core::new().unwrap();
+    let handle = reactor.handle();
+    let endpoints = uds::listen(&path, LineCodec::new(), || WhoAmI).unwrap();
+    let handle_cloned = handle.clone();
+    let accepted = endpoints
+        .for_each(move |endpoint| {
+            let (_client, finished) = process_start(endpoint.unwrap().start(&handle_cloned));
+            handle_cloned.spawn(finished.map_err(|_| ()));
+            Ok(())
+        })
+        .map_err(|e| panic!("Error: {}", e));
+    handle.spawn(accepted);
+    let endpoint = reactor
+        .run(uds::connect(&path, LineCodec::new(), Empty))
+        .unwrap();
+    let (client, _finished) = endpoint.start(&handle);
+    // Both sides are this process
+    let credentials = client.server_ctl().peer_credentials().unwrap();
+    if cfg!(any(target_os = "linux", target_os = "android")) {
+        assert_eq!(Some(std::process::id()), credentials.pid);
+    }
+    let called = client
+        .call("whoami".to_owned(), None, None)
+        .and_then(|(_client, answered)| answered);
+    let response = reactor.run(called).unwrap().unwrap();
+    assert_eq!(
+        json!([credentials.pid, credentials.uid, credentials.gid]),
+        response.result.unwrap()
+    );
+    std::fs::remove_file(&path).unwrap();
+}
+
+/// The client uses the configured ID generator and the response carries the ID back.
+#[test]
+fn sequential_ids() {
+    let (mut reactor, s1, s2) = prepare();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let (_client, server_finished) =
+            process_start(Endpoint::new(s1, AnswerServer).start(&handle));
+        let (client, client_endpoint_finished) = process_start(
+            Endpoint::client_only(s2)
+                .id_generator(SequentialIds::new(100))
+                .start(&handle),
+        );
+        client
+            .call("test".to_owned(), None, None)
+            .and_then(|(_client, answered)| answered)
+            .map(|response| assert_eq!(Id::Number(100), response.unwrap().id))
+            .join3(server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// A peer that turns the string IDs into numbers still gets its responses matched.
+#[test]
+fn lenient_ids() {
+    let (mut reactor, s1, s2) = prepare();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let mut last = 0;
+        let (client, client_endpoint_finished) = process_start(
+            Endpoint::client_only(s2)
+                .id_generator(move || {
+                    last += 1;
+                    Id::String(last.to_string())
+                })
+                .id_matching(IdMatching::Lenient)
+                .start(&handle),
+        );
+        // Play the part of a peer that mangles the IDs
+        let peer = s1.into_future()
+            .map_err(|(err, _s1)| err)
+            .and_then(|(request, s1)| {
+                let mut reply = match request.unwrap().unwrap() {
+                    Message::Request(request) => {
+                        assert_eq!(Id::from("1"), request.id);
+                        request.reply(json!(42))
+                    },
+                    other => panic!("Unexpected message {:?}", other),
+                };
+                if let Message::Response(ref mut response) = reply {
+                    response.id = Id::Number(1);
+                }
+                s1.send(reply).map(drop)
+            });
+        client
+            .call("test".to_owned(), None, None)
+            .and_then(|(_client, answered)| answered)
+            .map(|response| assert_eq!(json!(42), response.unwrap().result.unwrap()))
+            .join3(peer, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// A call with the ID of another waiting one is refused and not sent at all.
+///
+/// The first call still gets its own answer. Only the usual way to write a number is considered
+/// the same ID as the number, so `"01"` doesn't clash with `1`, but `"1"` does.
+#[test]
+fn duplicate_ids() {
+    let (mut reactor, s1, s2) = prepare();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let mut ids = vec![
+            Id::from("01"),
+            Id::Number(1),
+            Id::from("1"),
+            Id::Number(1),
+            Id::Number(2),
+        ].into_iter();
+        let (client, client_endpoint_finished) = process_start(
+            Endpoint::client_only(s2)
+                .id_generator(move || ids.next().unwrap())
+                .id_matching(IdMatching::Lenient)
+                .start(&handle),
+        );
+        // Answer each request by its method name, the refused ones never come
+        let reply = |msg: Message| match msg {
+            Message::Request(request) => request.reply(json!(request.method)),
+            other => panic!("Unexpected message {:?}", other),
+        };
+        let (sink, stream) = s1.split();
+        let peer = stream
+            .take(3)
+            .map(move |msg| match msg.unwrap() {
+                Message::Batch(batch) => Message::Batch(batch.into_iter().map(reply).collect()),
+                msg => reply(msg),
+            })
+            .forward(sink)
+            .map(drop);
+        let refused = |id: Id| {
+            move |result: Result<Option<Response>, IoError>| -> Result<(), IoError> {
+                let err = result.unwrap_err();
+                assert_eq!(ErrorKind::AlreadyExists, err.kind());
+                let duplicate = err.get_ref().unwrap().downcast_ref::<DuplicateId>().unwrap();
+                assert_eq!(DuplicateId(id), *duplicate);
+                Ok(())
+            }
+        };
+        let answered = |answer: Option<Response>, method: &str| {
+            assert_eq!(json!(method), answer.unwrap().result.unwrap());
+        };
+        client
+            .call("a".to_owned(), None, None)
+            .and_then(|(client, a)| {
+                client
+                    .call("b".to_owned(), None, None)
+                    .map(|(client, b)| (client, a.join(b)))
+            })
+            .and_then(move |(client, ab)| {
+                // With a timeout, which never starts
+                client
+                    .call("c".to_owned(), None, Some(Duration::new(10, 0)))
+                    .and_then(move |(client, c)| {
+                        c.then(refused(Id::from("1"))).map(|()| (client, ab))
+                    })
+            })
+            .and_then(move |(client, ab)| {
+                let mut batch = client.batch();
+                let d = batch.call("d".to_owned(), None, None);
+                let e = batch.call("e".to_owned(), None, None);
+                batch
+                    .send()
+                    .and_then(move |_client| {
+                        d.then(refused(Id::Number(1))).and_then(|()| ab.join(e))
+                    })
+            })
+            .map(move |((a, b), e)| {
+                answered(a, "a");
+                answered(b, "b");
+                answered(e, "e");
+            })
+            .join3(peer, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// Call an RPC from a different thread than the one running the reactor.
+#[test]
+fn other_thread() {
+    fn assert_send_sync<T: Send + Sync>() {}
+    assert_send_sync::<Client>();
+    assert_send_sync::<ServerCtl>();
+
+    let (mut reactor, s1, s2) = prepare();
+    let (all, caller) = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let (_client, server_finished) =
+            process_start(Endpoint::new(s1, AnswerServer).start(&handle));
+        let (client, client_endpoint_finished) =
+            process_start(Endpoint::client_only(s2).start(&handle));
+        let caller = thread::spawn(move || {
+            client
+                .call("test".to_owned(), None, Some(Duration::new(10, 0)))
+                .and_then(|(_client, answered)| answered)
+                .wait()
+                .unwrap()
+        });
+        (server_finished.join(client_endpoint_finished), caller)
+    };
+    reactor.run(all).unwrap();
+    let response = caller.join().unwrap();
+    assert_eq!(json!(42), response.unwrap().result.unwrap());
+}
+
+/// Build the endpoints directly from the IO objects, not framed streams.
+///
+/// One side gets the whole connection, the other is split into its read and write halves.
+#[test]
+fn from_io() {
+    let (mut reactor, s1, s2) = prepare_raw();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        // The same type as when framed manually
+        let endpoint: Endpoint<Framed<TcpStream, LineCodec>, _> =
+            Endpoint::from_io(s1, LineCodec::new(), AnswerServer);
+        let (_client, server_finished) = process_start(endpoint.start(&handle));
+        let (reader, writer) = s2.split();
+        let (client, client_endpoint_finished) = process_start(
+            Endpoint::from_halves(reader, writer, LineCodec::new(), Empty).start(&handle),
+        );
+        client
+            .call("test".to_owned(), None, None)
+            .and_then(|(_client, answered)| answered)
+            .map(|response| assert_eq!(json!(42), response.unwrap().result.unwrap()))
+            .join3(server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// Talk JSON-RPC 1.0 to each other.
+///
+/// The client starts in 1.0 and the server answers in the same version (with an error, as it
+/// doesn't know the method).
+#[test]
+fn v1() {
+    let (mut reactor, s1, s2) = prepare_raw();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let parser = Parser::new().accept_v1(true);
+        let (_client, server_finished) = process_start(
+            Endpoint::from_io(
+                s1,
+                LineCodec::with_parser(parser.clone()),
+                AnotherServer(handle.clone(), Cell::new(1)),
+            ).start(&handle),
+        );
+        let (client, client_endpoint_finished) = process_start(
+            Endpoint::client_only(s2.framed(LineCodec::with_parser(parser)))
+                .version(Version::V1)
+                .start(&handle),
+        );
+        client
+            .call("wrong".to_owned(), None, None)
+            .and_then(|(_client, answered)| answered)
+            .map(|response| {
+                let response = response.unwrap();
+                assert_eq!(Version::V1, response.version());
+                assert_eq!(-32_601, response.result.unwrap_err().code);
+            })
+            .join3(server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// Invalid batches from the specification get the invalid request errors.
+///
+/// An empty batch gets a single error, other invalid batches (including nested ones) get an error
+/// for each element.
+#[test]
+fn invalid_batches() {
+    let (mut reactor, s1, mut s2) = prepare();
+    s2.get_mut().write_all(b"[]\n[1, 2, 3]\n[[]]\n").unwrap();
+    let all = {
+        let handle = reactor.handle();
+        let (_client, server_finished) = process_start(
+            Endpoint::new(s1, AnotherServer(handle.clone(), Cell::new(1))).start(&handle),
+        );
+        let invalid = json!({
+            "jsonrpc": "2.0",
+            "error": {
+                "code": -32_600,
+                "message": "Invalid request",
+            },
+            "id": null,
+        });
+        let expected = vec![
+            invalid.clone(),
+            json!([invalid, invalid, invalid]),
+            json!([invalid]),
+        ];
+        s2.take(3)
+            .map(|msg| serde_json::to_value(msg.unwrap()).unwrap())
+            .collect()
+            .map(move |replies| assert_eq!(expected, replies))
+            .join(server_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// The invalid requests get their IDs back in the errors, if they can be recovered.
+///
+/// The broken responses don't, their IDs belong to the other side.
+#[test]
+fn unmatched_id() {
+    let (mut reactor, s1, mut s2) = prepare();
+    s2.get_mut()
+        .write_all(
+            b"{\"jsonrpc\": \"2.0\", \"id\": 5, \"resul\": 1}\n\
+              {\"jsonrpc\": \"2.0\", \"method\": \"x\", \"id\": 7, \"extra\": 1}\n",
+        )
+        .unwrap();
+    let all = {
+        let handle = reactor.handle();
+        let (_client, server_finished) = process_start(
+            Endpoint::new(s1, AnotherServer(handle.clone(), Cell::new(1))).start(&handle),
+        );
+        s2.take(2)
+            .collect()
+            .map(|replies| {
+                let ids = replies
+                    .into_iter()
+                    .map(|reply| match reply.unwrap() {
+                        Message::Response(response) => {
+                            assert_eq!(-32_600, response.result.unwrap_err().code);
+                            response.id
+                        },
+                        other => panic!("Unexpected message {:?}", other),
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(vec![Id::Null, Id::Number(7)], ids);
+            })
+            .join(server_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// A too large message gets an error and the connection goes on.
+#[test]
+fn too_large() {
+    let (mut reactor, s1, mut s2) = prepare();
+    let mut input = vec![b' '; 1000];
+    input.extend_from_slice(b"\n{\"jsonrpc\": \"2.0\", \"method\": \"wrong\", \"id\": 2}\n");
+    s2.get_mut().write_all(&input).unwrap();
+    let all = {
+        let handle = reactor.handle();
+        let parser = Parser::new().max_size(Some(100));
+        let (_client, server_finished) = process_start(
+            Endpoint::from_io(
+                s1.into_inner(),
+                LineCodec::with_parser(parser),
+                AnotherServer(handle.clone(), Cell::new(1)),
+            ).start(&handle),
+        );
+        let expected = vec![
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32_600,
+                    "message": "Invalid request",
+                    "data": "Message too large",
+                },
+                "id": null,
+            }),
+            json!({
+                "jsonrpc": "2.0",
+                "error": {
+                    "code": -32_601,
+                    "message": "Method not found",
+                    "data": "wrong",
+                },
+                "id": 2,
+            }),
+        ];
+        s2.take(2)
+            .map(|msg| serde_json::to_value(msg.unwrap()).unwrap())
+            .collect()
+            .map(move |replies| assert_eq!(expected, replies))
+            .join(server_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// Noise on the line gets a single parse error and the following request is still answered.
+#[test]
+fn boundary_noise() {
+    let (mut reactor, s1, mut s2) = prepare();
+    s2.get_mut()
+        .write_all(b"\x00%$#{]] {\"jsonrpc\": \"2.0\", \"method\": \"wrong\", \"id\": 3}")
+        .unwrap();
+    let all = {
+        let handle = reactor.handle();
+        let (_client, server_finished) = process_start(
+            Endpoint::from_io(
+                s1.into_inner(),
+                BoundaryCodec::new(),
+                AnotherServer(handle.clone(), Cell::new(1)),
+            ).start(&handle),
+        );
+        s2.take(2)
+            .map(|msg| match msg.unwrap() {
+                Message::Response(response) => {
+                    (response.id, response.result.unwrap_err().code)
+                },
+                other => panic!("Unexpected message {:?}", other),
+            })
+            .collect()
+            .map(|replies| {
+                assert_eq!(vec![(Id::Null, -32_700), (Id::Number(3), -32_601)], replies)
+            })
+            .join(server_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
 /// Send a notification to the server.
 #[test]
 fn notification() {
@@ -159,7 +1031,7 @@ impl Server for AnotherServer {
     type RpcCallResult = Box<Future<Item = bool, Error = RpcError>>;
     type NotificationResult = Result<(), ()>;
     fn rpc(
-        &self, ctl: &ServerCtl, method: &str, params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, params: &Option<Params>
     ) -> Option<Self::RpcCallResult> {
         let mut num = self.1.get();
         num -= 1;
@@ -168,7 +1040,7 @@ impl Server for AnotherServer {
             ctl.terminate();
         }
         if method == "timeout" {
-            let params: Vec<u64> = from_value(params.as_ref().unwrap().clone()).unwrap();
+            let params: Vec<u64> = params.as_ref().unwrap().parse().unwrap();
             let timeout = Timeout::new(Duration::new(params[0], params[1] as u32), &self.0)
                 .unwrap()
                 .map(|_| true)
@@ -206,6 +1078,7 @@ fn wrong_method() {
                         code: -32_601,
                         message: "Method not found".to_owned(),
                         data: Some(json!("wrong")),
+                        extensions: Default::default(),
                     },
                     response.unwrap().result.unwrap_err()
                 );
@@ -230,7 +1103,7 @@ fn timeout() {
         client
             .call(
                 "timeout".to_owned(),
-                Some(json!([3, 0])),
+                Some(Params::Positional(vec![json!(3), json!(0)])),
                 Some(Duration::new(1, 0)),
             )
             .and_then(|(_client, answered)| answered)
@@ -255,7 +1128,7 @@ fn delayed() {
         client
             .call(
                 "timeout".to_owned(),
-                Some(json!([0, 500_000_000])),
+                Some(Params::Positional(vec![json!(0), json!(500_000_000)])),
                 Some(Duration::new(1, 0)),
             )
             .and_then(|(_client, answered)| answered)
@@ -277,7 +1150,11 @@ fn client_only() {
         let (client, client_endpoint_finished) =
             process_start(Endpoint::client_only(s2).start(&handle));
         client
-            .call("timeout".to_owned(), Some(json!([0, 500_000_000])), None)
+            .call(
+                "timeout".to_owned(),
+                Some(Params::Positional(vec![json!(0), json!(500_000_000)])),
+                None,
+            )
             .and_then(|(_client, answered)| answered)
             .map(move |response| {
                 response.as_ref().unwrap();
@@ -306,7 +1183,11 @@ fn parallel() {
         let first_finished_cloned = first_finished.clone();
         let client1_finished = client
             .clone()
-            .call("timeout".to_owned(), Some(json!([0, 500_000_000])), None)
+            .call(
+                "timeout".to_owned(),
+                Some(Params::Positional(vec![json!(0), json!(500_000_000)])),
+                None,
+            )
             .and_then(|(_client, answered)| answered)
             .map(move |response| {
                 assert!(response.unwrap().result.unwrap().as_bool().unwrap());
@@ -321,6 +1202,7 @@ fn parallel() {
                         code: -32_601,
                         message: "Method not found".to_owned(),
                         data: Some(json!("wrong")),
+                        extensions: Default::default(),
                     },
                     response.unwrap().result.unwrap_err()
                 );
@@ -345,7 +1227,11 @@ fn seq() {
         let (client, _client_endpoint_finished) =
             process_start(Endpoint::client_only(s2).start(&handle));
         client
-            .call("timeout".to_owned(), Some(json!([0, 500_000_000])), None)
+            .call(
+                "timeout".to_owned(),
+                Some(Params::Positional(vec![json!(0), json!(500_000_000)])),
+                None,
+            )
             .and_then(|(client, answered)| {
                 let first_finished = Rc::new(Cell::new(false));
                 let first_finished_cloned = first_finished.clone();
@@ -358,6 +1244,7 @@ fn seq() {
                                 code: -32_601,
                                 message: "Method not found".to_owned(),
                                 data: Some(json!("wrong")),
+                                extensions: Default::default(),
                             },
                             response.unwrap().result.unwrap_err()
                         );
@@ -395,7 +1282,11 @@ fn kill() {
             process_start(Endpoint::client_only(s2).start(&handle));
         let client1_finished = client
             .clone()
-            .call("timeout".to_owned(), Some(json!([0, 500_000_000])), None)
+            .call(
+                "timeout".to_owned(),
+                Some(Params::Positional(vec![json!(0), json!(500_000_000)])),
+                None,
+            )
             .and_then(|(_client, answered)| answered)
             .then(|response| {
                 // This answer should not arrive, as the connection is killed before
@@ -430,7 +1321,11 @@ fn kill_client() {
         let ctl = client.server_ctl().clone();
         let client1_finished = client
             .clone()
-            .call("timeout".to_owned(), Some(json!([0, 500_000_000])), None)
+            .call(
+                "timeout".to_owned(),
+                Some(Params::Positional(vec![json!(0), json!(500_000_000)])),
+                None,
+            )
             .and_then(|(_client, answered)| answered)
             .then(|response| {
                 // This answer should not arrive, as the connection is killed before
@@ -446,6 +1341,7 @@ fn kill_client() {
                         code: -32_601,
                         message: "Method not found".to_owned(),
                         data: Some(json!("wrong")),
+                        extensions: Default::default(),
                     },
                     response.unwrap().result.unwrap_err()
                 );
@@ -464,7 +1360,7 @@ impl Server for MutualServer {
     type RpcCallResult = Box<Future<Item = Value, Error = RpcError>>;
     type NotificationResult = Result<(), ()>;
     fn rpc(
-        &self, ctl: &ServerCtl, method: &str, _params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, _params: &Option<Params>
     ) -> Option<Self::RpcCallResult> {
         if method == "ask" {
             let result = ctl.client()
@@ -478,7 +1374,7 @@ impl Server for MutualServer {
         }
     }
     fn notification(
-        &self, ctl: &ServerCtl, method: &str, _params: &Option<Value>
+        &self, ctl: &ServerCtl, method: &str, _params: &Option<Params>
     ) -> Option<Self::NotificationResult> {
         if method == "terminate" {
             ctl.terminate();
@@ -510,6 +1406,26 @@ fn mutual() {
     reactor.run(all).unwrap();
 }
 
+/// Check the connection gets closed if nothing comes for a long time.
+///
+/// The other side sends only a part of a message, which shouldn't count as activity.
+#[test]
+fn idle_timeout() {
+    let (mut reactor, s1, mut s2) = prepare();
+    s2.get_mut().write_all(b"{\"jsonrpc\": \"2.0\", ").unwrap();
+    let all = {
+        let handle = reactor.handle();
+        let (_c, s_fin) = Endpoint::new(s1, AnswerServer)
+            .idle_timeout(Duration::from_millis(300))
+            .start(&handle);
+        s_fin
+    };
+    let err = reactor.run(all).unwrap_err();
+    assert_eq!(ErrorKind::TimedOut, err.kind());
+    assert!(err.get_ref().unwrap().is::<IdleTimeout>());
+    drop(s2);
+}
+
 /// Check the server terminates if we drop the other end.
 #[test]
 fn conn_terminate() {
@@ -523,4 +1439,94 @@ fn conn_terminate() {
     reactor.run(all).unwrap();
 }
 
-// TODO: Test the batches (we can't call batches now, can we?)
+/// Send a batch with two RPCs and a notification and check both answers get routed back.
+#[test]
+fn batch() {
+    let (mut reactor, s1, s2) = prepare();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let (_client, server_finished) = process_start(
+            Endpoint::new(s1, AnotherServer(handle.clone(), Cell::new(2)))
+                .parallel(2)
+                .start(&handle),
+        );
+        let (client, client_endpoint_finished) =
+            process_start(Endpoint::client_only(s2).start(&handle));
+        let mut batch = client.batch();
+        let timeout_finished = batch
+            .call(
+                "timeout".to_owned(),
+                Some(Params::Positional(vec![json!(0), json!(100_000_000)])),
+                None,
+            )
+            .map(|response| assert!(response.unwrap().result.unwrap().as_bool().unwrap()));
+        batch.notify("notif".to_owned(), None);
+        let wrong_finished = batch.call("wrong".to_owned(), None, None).map(|response| {
+            assert_eq!(
+                RpcError {
+                    code: -32_601,
+                    message: "Method not found".to_owned(),
+                    data: Some(json!("wrong")),
+                    extensions: Default::default(),
+                },
+                response.unwrap().result.unwrap_err()
+            );
+        });
+        let sent = batch.send().map(|_client| ());
+        sent.join5(
+            timeout_finished,
+            wrong_finished,
+            server_finished,
+            client_endpoint_finished,
+        )
+    };
+    reactor.run(all).unwrap();
+}
+
+/// A batch dropped without being sent makes its calls fail and doesn't keep the endpoint alive.
+#[test]
+fn batch_unsent() {
+    let (mut reactor, s1, s2) = prepare();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let (_client, server_finished) =
+            process_start(Endpoint::new(s1, AnswerServer).start(&handle));
+        let (client, client_endpoint_finished) =
+            process_start(Endpoint::client_only(s2).start(&handle));
+        let mut batch = client.batch();
+        let answered = batch.call("test".to_owned(), None, None).then(|result| {
+            assert!(result.is_err());
+            Ok(())
+        });
+        drop(batch);
+        answered.join3(server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}
+
+/// The timeouts of batched calls start only once the batch is sent.
+#[test]
+fn batch_timeout() {
+    let (mut reactor, s1, s2) = prepare();
+    let all = {
+        // Run in a sub-block, so we drop all the clients, etc.
+        let handle = reactor.handle();
+        let (_client, server_finished) =
+            process_start(Endpoint::new(s1, AnswerServer).start(&handle));
+        let (client, client_endpoint_finished) =
+            process_start(Endpoint::client_only(s2).start(&handle));
+        let mut batch = client.batch();
+        let answered = batch
+            .call("test".to_owned(), None, Some(Duration::from_millis(500)))
+            .map(|response| assert_eq!(json!(42), response.unwrap().result.unwrap()));
+        // Wait longer than the timeout before sending
+        let sent = Timeout::new(Duration::from_secs(1), &handle)
+            .unwrap()
+            .and_then(move |_| batch.send())
+            .map(|_client| ());
+        sent.join4(answered, server_finished, client_endpoint_finished)
+    };
+    reactor.run(all).unwrap();
+}